@@ -0,0 +1,18 @@
+#!/bin/sh
+# Render the simulated mood timeline offline and compare it with the reference render.
+# Pass --bless to replace the reference with a fresh render after an intended change. If
+# there's no reference yet, the first render becomes it.
+set -eu
+cd "$(dirname "$0")/.."
+
+reference=assets/music/reference-render.wav
+output=target/music-render.wav
+
+if [ "${1:-}" = "--bless" ] || [ ! -f "$reference" ]; then
+    cargo run --release -- --render-music "$reference"
+    echo "Wrote the reference render to $reference; commit it to check later renders against"
+    exit 0
+fi
+
+mkdir -p target
+cargo run --release -- --render-music "$output" --reference "$reference"
@@ -0,0 +1,81 @@
+use crate::{
+    markers::{Breakable, BrokenVariant},
+    state::AppState,
+};
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+/// Sent when something bursts through a [`Breakable`], so that other systems can react
+#[derive(Message, Debug, Clone, Copy)]
+pub struct BreakableBroken {
+    pub breakable_entity: Entity,
+    pub breaker_entity: Entity,
+    pub impact_speed: f32,
+}
+
+pub struct BreakablePlugin;
+
+impl Plugin for BreakablePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<BreakableBroken>()
+            .add_systems(Update, break_breakables.run_if(in_state(AppState::Game)));
+    }
+}
+
+/// Find the [`Breakable`] that a collider belongs to, which may be the collider's entity or
+/// one of its ancestors (Skein components sit on the object, colliders on its mesh).
+pub fn find_breakable<'a>(
+    collider_entity: Entity,
+    breakables: &'a Query<&Breakable>,
+    parents: &Query<&ChildOf>,
+) -> Option<(Entity, &'a Breakable)> {
+    std::iter::once(collider_entity)
+        .chain(parents.iter_ancestors(collider_entity))
+        .find_map(|entity| {
+            breakables
+                .get(entity)
+                .ok()
+                .map(|breakable| (entity, breakable))
+        })
+}
+
+fn break_breakables(
+    mut commands: Commands,
+    mut messages: MessageReader<BreakableBroken>,
+    breakables: Query<&Breakable>,
+    children_query: Query<&Children>,
+    broken_variants: Query<Entity, With<BrokenVariant>>,
+) {
+    let mut already_broken: Vec<Entity> = Vec::new();
+    for message in messages.read() {
+        let root = message.breakable_entity;
+        if already_broken.contains(&root) || !breakables.contains(root) {
+            continue;
+        }
+        already_broken.push(root);
+        println!(
+            "Breakable {} broken by {} at speed {}",
+            root, message.breaker_entity, message.impact_speed
+        );
+
+        let broken_variant_roots: Vec<Entity> = children_query
+            .iter_descendants(root)
+            .filter(|entity| broken_variants.contains(*entity))
+            .collect();
+        let broken_variant_parts: Vec<Entity> = broken_variant_roots
+            .iter()
+            .flat_map(|entity| {
+                std::iter::once(*entity).chain(children_query.iter_descendants(*entity))
+            })
+            .collect();
+
+        commands.entity(root).remove::<Breakable>();
+        for entity in std::iter::once(root).chain(children_query.iter_descendants(root)) {
+            if broken_variant_roots.contains(&entity) {
+                commands.entity(entity).insert(Visibility::Inherited);
+            } else if !broken_variant_parts.contains(&entity) {
+                commands.entity(entity).remove::<(Collider, Mesh3d)>();
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{
+    controller::{CharacterController, Facing, PlayerController},
+    markers::CameraFocus,
+};
+use bevy::prelude::*;
+
+/// How far the look-ahead focus leads the player, so that the camera shows more of where
+/// they're heading. It sits ahead in the direction faced, further the faster they run, and
+/// drops below them while they fall so that the landing comes into view.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct LookAheadParams {
+    /// Distance ahead of the player at full speed
+    pub lead_distance: f32,
+
+    /// Proportion of the lead distance kept while standing still
+    pub idle_lead_fraction: f32,
+
+    /// Horizontal speed at which the full lead distance applies
+    pub full_lead_speed: f32,
+
+    /// Distance below the player per unit of falling speed
+    pub falling_bias: f32,
+
+    /// The most the focus will drop below the player while falling
+    pub max_falling_bias: f32,
+
+    /// Rate at which the focus catches up with where it should be; higher is snappier
+    pub smoothing: f32,
+}
+
+impl Default for LookAheadParams {
+    fn default() -> Self {
+        Self {
+            lead_distance: 4.0,
+            idle_lead_fraction: 0.3,
+            full_lead_speed: 5.0,
+            falling_bias: 0.3,
+            max_falling_bias: 4.0,
+            smoothing: 3.0,
+        }
+    }
+}
+
+/// The entity in an interactive conversation with the player, if any. Whatever runs the
+/// conversation sets this, and the look-ahead focus moves onto that entity until it's cleared.
+#[derive(Resource, Default)]
+pub struct ConversationPartner(pub Option<Entity>);
+
+/// The invisible camera focus that hovers in front of the player
+#[derive(Component, Default)]
+#[require(CameraFocus = CameraFocus::Active, Transform)]
+pub struct LookAheadFocus {
+    placed: bool,
+}
+
+pub fn spawn_look_ahead_focus(mut commands: Commands) {
+    commands.spawn((Name::new("Look-ahead focus"), LookAheadFocus::default()));
+}
+
+pub fn update_look_ahead_focus(
+    mut focus_query: Query<(&mut LookAheadFocus, &mut Transform), Without<PlayerController>>,
+    player_query: Query<(&Transform, &CharacterController, &Facing), With<PlayerController>>,
+    partner_query: Query<&GlobalTransform>,
+    partner: Res<ConversationPartner>,
+    params: Res<LookAheadParams>,
+    time: Res<Time>,
+) {
+    let Ok((player_transform, controller, facing)) = player_query.single() else {
+        return;
+    };
+    let partner_translation = partner
+        .0
+        .and_then(|entity| partner_query.get(entity).ok())
+        .map(|partner_transform| partner_transform.translation());
+    let target = match partner_translation {
+        Some(translation) => translation,
+        None => {
+            let velocity = controller.velocity();
+            let speed_ratio = (velocity.x.abs() / params.full_lead_speed).clamp(0.0, 1.0);
+            let lead = params.lead_distance
+                * (params.idle_lead_fraction + (1.0 - params.idle_lead_fraction) * speed_ratio);
+            let drop = (-velocity.y * params.falling_bias).clamp(0.0, params.max_falling_bias);
+            player_transform.translation + lead * facing.direction() - drop * Vec3::Y
+        }
+    };
+
+    let catch_up = 1.0 - (-params.smoothing * time.delta_secs()).exp();
+    for (mut focus, mut transform) in focus_query.iter_mut() {
+        transform.translation = match focus.placed {
+            true => transform.translation.lerp(target, catch_up),
+            false => target,
+        };
+        focus.placed = true;
+    }
+}
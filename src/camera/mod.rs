@@ -0,0 +1,235 @@
+mod look_ahead;
+mod regions;
+mod shake;
+
+pub use look_ahead::{ConversationPartner, LookAheadParams};
+pub use shake::{CameraImpulse, CameraImpulsePresets, CameraShakeSettings};
+
+use crate::{
+    app_draw_layer,
+    controller::PlayerController,
+    markers::{CameraFocus, CameraRegion, VolumeExtents},
+    state::AppState,
+};
+use avian3d::schedule::PhysicsSystems;
+use bevy::{camera::visibility::RenderLayers, prelude::*, transform::TransformSystems};
+use bevy_inspector_egui::bevy_egui::PrimaryEguiContext;
+use look_ahead::{spawn_look_ahead_focus, update_look_ahead_focus};
+use regions::apply_camera_regions;
+use shake::{
+    CameraShake, apply_camera_shake, on_attachment_inserted, on_special_move_inserted,
+    receive_camera_impulses, send_breakable_impulses,
+};
+
+const CAMERA_DISTANCE: f32 = 20.0;
+
+/// How the game camera frames its foci. The camera keeps every active focus, and every
+/// proximity focus near enough to one, inside its view with some margin to spare, dollying
+/// back from its closest distance as far as it needs to. It gets there by springs on both
+/// its XY movement and its zoom, so that it carries momentum rather than snapping.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct CameraFramingParams {
+    /// World-space space kept between the framed foci and the edges of the view
+    pub margin: f32,
+
+    /// The distance the camera sits at when framing only a single point
+    pub min_distance: f32,
+
+    /// The furthest the camera will dolly back to fit everything in
+    pub max_distance: f32,
+
+    /// Spring stiffness pulling the camera across towards the centre of the framed box
+    pub xy_stiffness: f32,
+
+    /// Spring stiffness pulling the camera's distance towards the one that fits the box
+    pub zoom_stiffness: f32,
+}
+
+impl Default for CameraFramingParams {
+    fn default() -> Self {
+        Self {
+            margin: 3.0,
+            min_distance: CAMERA_DISTANCE,
+            max_distance: 2.5 * CAMERA_DISTANCE,
+            xy_stiffness: 30.0,
+            zoom_stiffness: 6.0,
+        }
+    }
+}
+
+/// The game camera's current velocity, with Z being the rate of dollying, and the shake
+/// offset on top of where framing last put it
+#[derive(Component, Default)]
+#[require(CameraShake)]
+pub struct FramingCamera {
+    velocity: Vec3,
+    shake_offset: Vec3,
+}
+
+pub struct GameCameraPlugin;
+
+impl Plugin for GameCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraFramingParams>()
+            .init_resource::<LookAheadParams>()
+            .init_resource::<ConversationPartner>()
+            .init_resource::<CameraImpulsePresets>()
+            .init_resource::<CameraShakeSettings>()
+            .add_message::<CameraImpulse>()
+            .add_observer(on_special_move_inserted)
+            .add_observer(on_attachment_inserted)
+            .add_systems(
+                Update,
+                send_breakable_impulses.run_if(in_state(AppState::Game)),
+            )
+            .add_systems(
+                OnEnter(AppState::Game),
+                (spawn_camera, spawn_look_ahead_focus),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    update_look_ahead_focus,
+                    update_camera,
+                    receive_camera_impulses,
+                    apply_camera_shake,
+                )
+                    .chain()
+                    .run_if(in_state(AppState::Game))
+                    .after(PhysicsSystems::Writeback)
+                    .before(TransformSystems::Propagate),
+            );
+    }
+}
+
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn((
+        FramingCamera::default(),
+        Camera3d::default(),
+        Camera {
+            order: app_draw_layer::MAIN as isize,
+            ..default()
+        },
+        Transform::from_xyz(0.0, 0.0, CAMERA_DISTANCE).looking_at(Vec3::ZERO, Vec3::Y),
+        RenderLayers::layer(app_draw_layer::MAIN),
+    ));
+    commands.spawn((
+        Camera2d::default(),
+        Camera {
+            order: app_draw_layer::HUD as isize,
+            ..default()
+        },
+        RenderLayers::layer(app_draw_layer::HUD),
+        PrimaryEguiContext,
+    ));
+}
+
+/// The XY box the camera should keep in view: all active foci, plus any proximity focus
+/// within its distance of one of them
+fn framed_box(focus_query: &Query<(&Transform, &CameraFocus), Without<Camera>>) -> Option<Rect> {
+    let active_points: Vec<Vec2> = focus_query
+        .iter()
+        .filter(|(_, focus)| **focus == CameraFocus::Active)
+        .map(|(transform, _)| transform.translation.truncate())
+        .collect();
+    let proximity_points = focus_query.iter().filter_map(|(transform, focus)| {
+        let CameraFocus::Proximity { distance } = focus else {
+            return None;
+        };
+        let point = transform.translation.truncate();
+        active_points
+            .iter()
+            .any(|active_point| active_point.distance(point) <= *distance)
+            .then_some(point)
+    });
+    let mut points = active_points.iter().copied().chain(proximity_points);
+    let first_point = points.next()?;
+    Some(points.fold(
+        Rect::from_center_size(first_point, Vec2::ZERO),
+        |rect, point| rect.union_point(point),
+    ))
+}
+
+/// Move a value towards a target under a critically-damped spring, which carries momentum
+/// without overshooting
+fn spring_towards(
+    value: f32,
+    velocity: &mut f32,
+    target: f32,
+    stiffness: f32,
+    delta_time: f32,
+) -> f32 {
+    let damping = 2.0 * stiffness.sqrt();
+    *velocity += (stiffness * (target - value) - damping * *velocity) * delta_time;
+    value + *velocity * delta_time
+}
+
+fn update_camera(
+    mut camera_query: Query<
+        (&mut Transform, &mut FramingCamera, &Projection),
+        Without<CameraFocus>,
+    >,
+    focus_query: Query<(&Transform, &CameraFocus), Without<Camera>>,
+    player_query: Query<&Transform, (With<PlayerController>, Without<FramingCamera>)>,
+    region_query: Query<(&CameraRegion, &GlobalTransform, &VolumeExtents)>,
+    params: Res<CameraFramingParams>,
+    time: Res<Time>,
+) -> Result<(), BevyError> {
+    let (mut camera_transform, mut framing, projection) = camera_query.single_mut()?;
+    let Some(framed_box) = framed_box(&focus_query) else {
+        return Err("No focus found for game camera.".into());
+    };
+    let framed_box = framed_box.inflate(params.margin);
+
+    // Back off until the box fits the view both vertically and horizontally
+    let target_distance = match projection {
+        Projection::Perspective(perspective) => {
+            let half_fov_tan = (0.5 * perspective.fov).tan();
+            let half_size = framed_box.half_size();
+            let distance_for_height = half_size.y / half_fov_tan;
+            let distance_for_width = half_size.x / (half_fov_tan * perspective.aspect_ratio);
+            distance_for_height.max(distance_for_width)
+        }
+        _ => params.min_distance,
+    }
+    .clamp(params.min_distance, params.max_distance);
+
+    // Level regions around the player can then clamp, rail or zoom the camera
+    let target = framed_box.center().extend(target_distance);
+    let target = match player_query.single() {
+        Ok(player_transform) => {
+            apply_camera_regions(target, player_transform.translation, region_query.iter())
+        }
+        Err(_) => target,
+    };
+
+    let delta_time = time.delta_secs();
+    let current = camera_transform.translation - framing.shake_offset;
+    let mut velocity = framing.velocity;
+    camera_transform.translation = Vec3::new(
+        spring_towards(
+            current.x,
+            &mut velocity.x,
+            target.x,
+            params.xy_stiffness,
+            delta_time,
+        ),
+        spring_towards(
+            current.y,
+            &mut velocity.y,
+            target.y,
+            params.xy_stiffness,
+            delta_time,
+        ),
+        spring_towards(
+            current.z,
+            &mut velocity.z,
+            target.z,
+            params.zoom_stiffness,
+            delta_time,
+        ),
+    );
+    framing.velocity = velocity;
+    Ok(())
+}
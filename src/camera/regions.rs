@@ -0,0 +1,77 @@
+use crate::markers::{CameraRegion, VolumeExtents};
+use bevy::prelude::*;
+
+/// How strongly a region applies at a point: nothing outside it, rising smoothly to full
+/// strength at its blend distance in from the nearest edge
+fn region_weight(
+    point: Vec2,
+    region_translation: Vec2,
+    extents: &VolumeExtents,
+    blend_distance: f32,
+) -> f32 {
+    let relative = (point - region_translation).abs();
+    let depth = (extents.half_extent_x - relative.x).min(extents.half_extent_y - relative.y);
+    if depth <= 0.0 {
+        return 0.0;
+    }
+    if blend_distance <= 0.0 {
+        return 1.0;
+    }
+    let progress = (depth / blend_distance).min(1.0);
+    progress * progress * (3.0 - 2.0 * progress)
+}
+
+/// Where a region would put the camera, given where framing alone would put it (with Z
+/// being the camera's distance)
+fn constrain(region: &CameraRegion, target: Vec3) -> Vec3 {
+    let mut constrained = target;
+    if let Some(min_x) = region.min_x {
+        constrained.x = constrained.x.max(min_x);
+    }
+    if let Some(max_x) = region.max_x {
+        constrained.x = constrained.x.min(max_x);
+    }
+    if let Some(min_y) = region.min_y {
+        constrained.y = constrained.y.max(min_y);
+    }
+    if let Some(max_y) = region.max_y {
+        constrained.y = constrained.y.min(max_y);
+    }
+    if let Some(rail_y) = region.rail_y {
+        constrained.y = rail_y;
+    }
+    if let Some(distance) = region.distance {
+        constrained.z = distance;
+    }
+    constrained
+}
+
+/// Blend the framing target through every region the player is in. Larger regions apply
+/// first, so that smaller regions nested inside them have the final say.
+pub fn apply_camera_regions<'a>(
+    target: Vec3,
+    player_translation: Vec3,
+    regions: impl Iterator<Item = (&'a CameraRegion, &'a GlobalTransform, &'a VolumeExtents)>,
+) -> Vec3 {
+    let mut containing: Vec<(&CameraRegion, &VolumeExtents, f32)> = regions
+        .filter_map(|(region, region_transform, extents)| {
+            let weight = region_weight(
+                player_translation.truncate(),
+                region_transform.translation().truncate(),
+                extents,
+                region.blend_distance,
+            );
+            (weight > 0.0).then_some((region, extents, weight))
+        })
+        .collect();
+    containing.sort_by(|(_, a, _), (_, b, _)| {
+        let area_a = a.half_extent_x * a.half_extent_y;
+        let area_b = b.half_extent_x * b.half_extent_y;
+        area_b.total_cmp(&area_a)
+    });
+    containing
+        .into_iter()
+        .fold(target, |target, (region, _, weight)| {
+            target.lerp(constrain(region, target), weight)
+        })
+}
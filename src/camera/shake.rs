@@ -0,0 +1,200 @@
+use super::FramingCamera;
+use crate::{
+    breakable::BreakableBroken,
+    controller::{Attachment, PlayerController, SpecialMove},
+};
+use bevy::prelude::*;
+
+/// Gameplay moments that the camera reacts to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraImpulseKind {
+    HardLanding,
+    WallStick,
+    BreakableBroken,
+}
+
+/// Sent to make the game camera shake and punch in, by the amounts in its preset
+#[derive(Message, Debug, Clone, Copy)]
+pub struct CameraImpulse(pub CameraImpulseKind);
+
+#[derive(Reflect, Debug, Clone, Copy)]
+pub struct CameraImpulsePreset {
+    /// Trauma added, where 1.0 is the strongest possible shake
+    pub trauma: f32,
+
+    /// Zoom punch added, where 1.0 is the full punch distance
+    pub zoom_punch: f32,
+}
+
+/// How hard the camera reacts to each kind of [`CameraImpulse`]
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct CameraImpulsePresets {
+    pub hard_landing: CameraImpulsePreset,
+    pub wall_stick: CameraImpulsePreset,
+    pub breakable_broken: CameraImpulsePreset,
+}
+
+impl Default for CameraImpulsePresets {
+    fn default() -> Self {
+        Self {
+            hard_landing: CameraImpulsePreset {
+                trauma: 0.5,
+                zoom_punch: 0.6,
+            },
+            wall_stick: CameraImpulsePreset {
+                trauma: 0.15,
+                zoom_punch: 0.0,
+            },
+            breakable_broken: CameraImpulsePreset {
+                trauma: 0.7,
+                zoom_punch: 1.0,
+            },
+        }
+    }
+}
+
+impl CameraImpulsePresets {
+    fn preset(&self, kind: CameraImpulseKind) -> &CameraImpulsePreset {
+        match kind {
+            CameraImpulseKind::HardLanding => &self.hard_landing,
+            CameraImpulseKind::WallStick => &self.wall_stick,
+            CameraImpulseKind::BreakableBroken => &self.breakable_broken,
+        }
+    }
+}
+
+/// Trauma-based shake: the shake's strength is the square of the trauma, which decays
+/// steadily, so that small knocks barely register while big ones settle down smoothly.
+/// Reduced motion turns off both the shake and the zoom punch.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct CameraShakeSettings {
+    pub reduced_motion: bool,
+
+    /// Largest offset of the camera in X and Y at full trauma
+    pub max_offset: f32,
+
+    /// Largest roll of the camera in radians at full trauma
+    pub max_roll: f32,
+
+    /// Trauma lost per second
+    pub trauma_decay: f32,
+
+    /// How fast the shake wobbles
+    pub frequency: f32,
+
+    /// How far the camera moves in at full zoom punch
+    pub punch_distance: f32,
+
+    /// Zoom punch lost per second
+    pub punch_decay: f32,
+}
+
+impl Default for CameraShakeSettings {
+    fn default() -> Self {
+        Self {
+            reduced_motion: false,
+            max_offset: 0.6,
+            max_roll: 0.05,
+            trauma_decay: 1.5,
+            frequency: 18.0,
+            punch_distance: 2.0,
+            punch_decay: 5.0,
+        }
+    }
+}
+
+/// The shake and punch currently on the game camera
+#[derive(Component, Default)]
+pub struct CameraShake {
+    trauma: f32,
+    zoom_punch: f32,
+}
+
+pub fn on_special_move_inserted(
+    insert: On<Insert, SpecialMove>,
+    special_move_query: Query<&SpecialMove, With<PlayerController>>,
+    mut impulses: MessageWriter<CameraImpulse>,
+) {
+    if let Ok(SpecialMove::Landing { progress }) = special_move_query.get(insert.entity)
+        && *progress == 0.0
+    {
+        impulses.write(CameraImpulse(CameraImpulseKind::HardLanding));
+    }
+}
+
+pub fn on_attachment_inserted(
+    insert: On<Insert, Attachment>,
+    attachment_query: Query<&Attachment, With<PlayerController>>,
+    mut impulses: MessageWriter<CameraImpulse>,
+) {
+    if let Ok(Attachment::Walled { progress, .. }) = attachment_query.get(insert.entity)
+        && *progress == 0.0
+    {
+        impulses.write(CameraImpulse(CameraImpulseKind::WallStick));
+    }
+}
+
+pub fn send_breakable_impulses(
+    mut broken_messages: MessageReader<BreakableBroken>,
+    mut impulses: MessageWriter<CameraImpulse>,
+) {
+    if broken_messages.read().count() > 0 {
+        impulses.write(CameraImpulse(CameraImpulseKind::BreakableBroken));
+    }
+}
+
+pub fn receive_camera_impulses(
+    mut impulses: MessageReader<CameraImpulse>,
+    mut shake_query: Query<&mut CameraShake>,
+    presets: Res<CameraImpulsePresets>,
+    settings: Res<CameraShakeSettings>,
+) {
+    for CameraImpulse(kind) in impulses.read() {
+        if settings.reduced_motion {
+            continue;
+        }
+        let preset = presets.preset(*kind);
+        for mut shake in shake_query.iter_mut() {
+            shake.trauma = (shake.trauma + preset.trauma).min(1.0);
+            shake.zoom_punch = (shake.zoom_punch + preset.zoom_punch).min(1.0);
+        }
+    }
+}
+
+/// Smooth noise in -1 to 1, different for each seed
+fn wobble(time: f32, seed: f32) -> f32 {
+    let phase = time + 13.7 * seed;
+    (0.6 * phase.sin() + 0.3 * (phase * 2.3 + 1.1).sin() + 0.1 * (phase * 5.7 + 2.9).sin())
+        .clamp(-1.0, 1.0)
+}
+
+/// Offset the camera from where framing put it, remembering the offset so that framing
+/// can take it off again next frame
+pub fn apply_camera_shake(
+    mut camera_query: Query<(&mut Transform, &mut FramingCamera, &mut CameraShake)>,
+    settings: Res<CameraShakeSettings>,
+    time: Res<Time>,
+) {
+    let delta_time = time.delta_secs();
+    for (mut transform, mut framing, mut shake) in camera_query.iter_mut() {
+        if settings.reduced_motion {
+            *shake = CameraShake::default();
+        }
+        shake.trauma = (shake.trauma - settings.trauma_decay * delta_time).max(0.0);
+        shake.zoom_punch = (shake.zoom_punch - settings.punch_decay * delta_time).max(0.0);
+
+        let strength = shake.trauma * shake.trauma;
+        let wobble_time = settings.frequency * time.elapsed_secs();
+        let offset = Vec3::new(
+            settings.max_offset * strength * wobble(wobble_time, 0.0),
+            settings.max_offset * strength * wobble(wobble_time, 1.0),
+            -settings.punch_distance * shake.zoom_punch,
+        );
+        transform.translation += offset;
+        transform.rotation =
+            Quat::from_rotation_z(settings.max_roll * strength * wobble(wobble_time, 2.0));
+        framing.shake_offset = offset;
+    }
+}
@@ -1,10 +1,17 @@
 use super::MovementResult;
 use crate::controller::{
-    Attachment, Facing, PlayerController, SpecialMove, params::CharacterControllerParams,
+    Attachment, CharacterController, Facing, SpecialMove, params::CharacterControllerParams,
+};
+use avian3d::{
+    collision::collider::contact_query,
+    math::{Quaternion, Vector},
+    prelude::*,
 };
-use avian3d::{math::Quaternion, prelude::*};
 use bevy::prelude::*;
 
+/// Angle by which a ledge's normal must change before the attachment is updated to match it
+const LEDGE_CONTOUR_ANGLE_EPSILON: f32 = 0.01;
+
 pub fn check_aerial_hit_movement(
     spatial_queries: &Res<SpatialQueryPipeline>,
     entity_filter: &SpatialQueryFilter,
@@ -50,7 +57,7 @@ fn try_finding_ledge(
     collision_position: &Vec3,
     hit: &ShapeHitData,
 ) -> Option<Option<MovementResult>> {
-    let shape = PlayerController::collider(params.collider_skin_thickness);
+    let shape = CharacterController::collider(params.collider_skin_thickness);
     let ledge_cast_position = collision_position
         + params.ledge_grab_relative_y * Vec3::Y
         + params.ledge_grab_required_inset * hit.normal2;
@@ -129,6 +136,191 @@ fn try_finding_wall(
     None
 }
 
+/// Move along the edge of a grabbed ledge, following its contour.
+/// The player is swept along just above the ledge top, then pulled back against the wall below
+/// it and snapped to the height of the ledge top found beside it. If either the wall or the ledge
+/// top cannot be found, the player is left where the sweep ended so that the ledge is let go of.
+pub fn shimmy_along_ledge(
+    spatial_queries: &Res<SpatialQueryPipeline>,
+    entity_filter: &SpatialQueryFilter,
+    params: &Res<CharacterControllerParams>,
+    position: &Vec3,
+    attempted_displacement: &Vec3,
+    normal: &Vector,
+) -> MovementResult {
+    let Ok(direction) = Dir3::new(*attempted_displacement) else {
+        return MovementResult {
+            new_position: *position,
+            new_attachment: None,
+            new_velocity: None,
+        };
+    };
+
+    // Sweep along the ledge, raised a little so that a rising ledge top doesn't block it, and
+    // stopping short of anything in the way (e.g. an inside corner)
+    let shape = CharacterController::collider(params.collider_skin_thickness);
+    let sweep_position = position + params.ledge_grab_tolerance_y * Vec3::Y;
+    let config = ShapeCastConfig {
+        max_distance: attempted_displacement.length() + params.collider_skin_thickness,
+        ..default()
+    };
+    let mut new_position = match spatial_queries.cast_shape(
+        &shape,
+        sweep_position,
+        Quaternion::default(),
+        direction,
+        &config,
+        entity_filter,
+    ) {
+        Some(hit) => {
+            sweep_position + (hit.distance - params.collider_skin_thickness).max(0.0) * direction
+        }
+        None => sweep_position + attempted_displacement,
+    };
+
+    // Find the wall again, in case it curves away from or towards the player
+    let Some(wall_hit) = cast_for_ledge_wall(
+        spatial_queries,
+        entity_filter,
+        params,
+        &new_position,
+        normal,
+        params.ledge_cling_wall_probe_distance,
+    ) else {
+        return MovementResult {
+            new_position,
+            new_attachment: None,
+            new_velocity: None,
+        };
+    };
+    new_position -= (wall_hit.distance - params.collider_skin_thickness)
+        * normal.with_y(0.0).normalize_or_zero();
+    // Stay on the play plane whichever way the wall faces
+    new_position.z = position.z;
+    let new_normal = wall_hit.normal1;
+
+    // Follow the ledge top up or down
+    if let Some(ledge_ground) = cast_for_ledge_ground(
+        spatial_queries,
+        entity_filter,
+        params,
+        &new_position,
+        &new_normal,
+    ) {
+        new_position.y += params.ledge_grab_relative_y - ledge_ground.distance;
+    }
+
+    let new_attachment = match normal.angle_between(new_normal) > LEDGE_CONTOUR_ANGLE_EPSILON {
+        true => Some((Attachment::LedgeGrabbed { normal: new_normal }, None)),
+        false => None,
+    };
+    MovementResult {
+        new_position,
+        new_attachment,
+        new_velocity: None,
+    }
+}
+
+/// Cast horizontally towards the wall beneath a ledge the player is holding.
+/// The player hangs with its feet level with the ledge top, so the cast is made from lower down.
+pub fn cast_for_ledge_wall(
+    spatial_queries: &Res<SpatialQueryPipeline>,
+    entity_filter: &SpatialQueryFilter,
+    params: &Res<CharacterControllerParams>,
+    position: &Vec3,
+    normal: &Vector,
+    max_distance: f32,
+) -> Option<ShapeHitData> {
+    let direction = Dir3::new(-normal.with_y(0.0)).ok()?;
+    let shape = CharacterController::collider(params.collider_skin_thickness);
+    let config = ShapeCastConfig {
+        max_distance: max_distance + params.collider_skin_thickness,
+        ..default()
+    };
+    spatial_queries.cast_shape(
+        &shape,
+        position - params.ledge_grab_relative_y * Vec3::Y,
+        Quaternion::default(),
+        direction,
+        &config,
+        entity_filter,
+    )
+}
+
+/// Cast downwards from above a ledge the player is holding, looking for the top of it.
+/// The range allows for the ledge top rising or falling by the grab tolerance since the last step.
+/// A hit at zero distance means there's no room above the ledge, so it isn't counted.
+pub fn cast_for_ledge_ground(
+    spatial_queries: &Res<SpatialQueryPipeline>,
+    entity_filter: &SpatialQueryFilter,
+    params: &Res<CharacterControllerParams>,
+    position: &Vec3,
+    normal: &Vector,
+) -> Option<ShapeHitData> {
+    let shape = CharacterController::collider(params.collider_skin_thickness);
+    let ledge_cast_position = position + params.ledge_grab_relative_y * Vec3::Y
+        - params.ledge_grab_required_inset * normal.with_y(0.0).normalize_or_zero();
+    let config = ShapeCastConfig {
+        max_distance: params.ledge_grab_relative_y + 2.0 * params.ledge_grab_tolerance_y,
+        ..default()
+    };
+    spatial_queries
+        .cast_shape(
+            &shape,
+            ledge_cast_position,
+            Quaternion::default(),
+            Dir3::NEG_Y,
+            &config,
+            entity_filter,
+        )
+        .filter(|hit| hit.distance > 0.0)
+}
+
+/// Find how far the player must move to no longer overlap any collider, pushing out of the
+/// deepest overlap along its minimum-translation vector each time, and which collider the
+/// deepest overlap was with. Gives None when the player isn't overlapping anything.
+pub fn find_depenetration(
+    spatial_queries: &Res<SpatialQueryPipeline>,
+    entity_filter: &SpatialQueryFilter,
+    params: &Res<CharacterControllerParams>,
+    collider_query: &Query<(&Collider, &Position, &Rotation), Without<CharacterController>>,
+    position: &Vec3,
+) -> Option<(Vector, Entity)> {
+    let shape = CharacterController::collider(params.collider_skin_thickness);
+    let shape_rotation = Rotation::default();
+    let mut push = Vector::ZERO;
+    let mut deepest_entity = None;
+    for _ in 0..params.max_depenetration_iterations {
+        let deepest_contact = spatial_queries
+            .shape_intersections(&shape, position + push, shape_rotation.0, entity_filter)
+            .into_iter()
+            .filter_map(|entity| {
+                let (collider, collider_position, collider_rotation) =
+                    collider_query.get(entity).ok()?;
+                let contact = contact_query::contact(
+                    &shape,
+                    position + push,
+                    shape_rotation,
+                    collider,
+                    collider_position.0,
+                    *collider_rotation,
+                    0.0,
+                )
+                .ok()??;
+                Some((entity, contact))
+            })
+            .filter(|(_, contact)| contact.penetration > 0.0)
+            .max_by(|(_, a), (_, b)| a.penetration.total_cmp(&b.penetration));
+        let Some((entity, contact)) = deepest_contact else {
+            break;
+        };
+        push -= (contact.penetration + params.collider_skin_thickness)
+            * contact.global_normal1(&shape_rotation);
+        deepest_entity.get_or_insert(entity);
+    }
+    deepest_entity.map(|entity| (push, entity))
+}
+
 pub fn update_facing(from: &Facing, current_velocity: &Vec3) -> f32 {
     match Vec3::new(current_velocity.x, 0.0, 0.0).try_normalize() {
         Some(travel_facing) => match travel_facing.x > 0.0 {
@@ -1,4 +1,4 @@
-use crate::markers::WaterVolumeExtents;
+use crate::markers::VolumeExtents;
 use bevy::prelude::*;
 
 /// Process timestep of acceleration with terminal velocity.
@@ -51,7 +51,7 @@ pub fn float_modulus(mut value: f32, range: f32) -> f32 {
 pub fn inside_volume(
     point: &Vec3,
     volume_position: &Vec3,
-    volume: &WaterVolumeExtents,
+    volume: &VolumeExtents,
     skin_thickness: f32,
 ) -> bool {
     let relative_x = (point.x - volume_position.x).abs();
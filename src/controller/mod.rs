@@ -3,7 +3,7 @@ mod math;
 mod params;
 mod systems;
 
-use crate::markers::{CameraFocus, Player};
+use crate::markers::{CameraFocus, MovementModifierVolume, Player};
 use avian3d::{
     math::{Scalar, Vector},
     prelude::*,
@@ -18,6 +18,7 @@ pub const PLAYER_RADIUS: f32 = 0.5;
 
 const GROUNDING_PROXIMITY: Scalar = 0.4;
 const WALL_RETENTION_PROXIMITY: Scalar = 0.1;
+const POWER_UP_PICKUP_PROXIMITY: Scalar = 0.5;
 
 struct MovementResult {
     new_position: Vec3,
@@ -25,6 +26,113 @@ struct MovementResult {
     new_velocity: Option<Vector>,
 }
 
+#[derive(PartialEq, Clone, Copy)]
+enum JumpMode {
+    None,
+    Regular {
+        factor: Scalar,
+    },
+    Dive {
+        upward_factor: Scalar,
+        horizontal_factor: Scalar,
+    },
+    Shallow {
+        factor: Scalar,
+    },
+    AwayFromNormal {
+        normal: Vec3,
+        upward_impulse_factor: Scalar,
+    },
+    Climb {
+        normal: Vec3,
+    },
+}
+
+impl JumpMode {
+    fn context_action(&self) -> Option<ContextAction> {
+        match self {
+            Self::None => None,
+            Self::Regular { .. } | Self::Shallow { .. } => Some(ContextAction::Jump),
+            Self::Dive { .. } => Some(ContextAction::Dive),
+            Self::AwayFromNormal { .. } => Some(ContextAction::WallJump),
+            Self::Climb { .. } => Some(ContextAction::Climb),
+        }
+    }
+}
+
+/// What pressing a button would make the player do right now, as prompted in the HUD
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextAction {
+    Jump,
+    Dive,
+    WallJump,
+    Climb,
+    Roll,
+    Kick,
+    Stream,
+}
+
+impl ContextAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Jump => "Jump",
+            Self::Dive => "Dive",
+            Self::WallJump => "Wall Jump",
+            Self::Climb => "Climb",
+            Self::Roll => "Roll",
+            Self::Kick => "Kick",
+            Self::Stream => "Stream",
+        }
+    }
+}
+
+/// The actions that the player's jump and secondary buttons would perform this fixed step
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq)]
+pub struct ActionPrompts {
+    pub jump: Option<ContextAction>,
+    pub secondary: Option<ContextAction>,
+}
+
+/// How the rigid body under a character is moving, so that the character can be carried along
+struct SupportMotion {
+    centre: Vector,
+    linear_velocity: Vector,
+    angular_velocity: Vector,
+}
+
+impl SupportMotion {
+    /// Find the motion of the body that a supporting collider is attached to, if it has one.
+    /// Colliders without a body (like the static terrain) don't move, so give None.
+    fn find(
+        supporting_entity: Entity,
+        colliders: &Query<&ColliderOf>,
+        bodies: &Query<
+            (&Position, &LinearVelocity, &AngularVelocity),
+            Without<CharacterController>,
+        >,
+    ) -> Option<Self> {
+        let body_entity = colliders.get(supporting_entity).ok()?.body;
+        let (position, linear_velocity, angular_velocity) = bodies.get(body_entity).ok()?;
+        Some(Self {
+            centre: position.0,
+            linear_velocity: linear_velocity.0,
+            angular_velocity: angular_velocity.0,
+        })
+    }
+
+    /// Velocity of the body at a point, including that due to its rotation
+    fn velocity_at(&self, point: &Vector) -> Vector {
+        self.linear_velocity + self.angular_velocity.cross(point - self.centre)
+    }
+
+    /// Displacement of a point carried by the body over the coming physics step
+    fn displacement_at(&self, point: &Vector, delta_time: Scalar) -> Vector {
+        let arm = point - self.centre;
+        let rotation = Quat::from_scaled_axis(delta_time * self.angular_velocity);
+        delta_time * self.linear_velocity + rotation * arm - arm
+    }
+}
+
 #[derive(Component, Reflect, Debug)]
 #[reflect(Component)]
 #[component(
@@ -33,11 +141,23 @@ struct MovementResult {
     on_remove = Self::on_remove
 )]
 pub enum Attachment {
-    Grounded { normal: Vector },
-    LedgeGrabbed { normal: Vector },
-    Walled { normal: Vector, progress: f32 },
-    Submerged { water_volume_entity: Entity },
-    Floating { water_volume_entity: Entity },
+    Grounded {
+        normal: Vector,
+        supporting_entity: Entity,
+    },
+    LedgeGrabbed {
+        normal: Vector,
+    },
+    Walled {
+        normal: Vector,
+        progress: f32,
+    },
+    Submerged {
+        water_volume_entity: Entity,
+    },
+    Floating {
+        water_volume_entity: Entity,
+    },
 }
 
 impl Attachment {
@@ -53,12 +173,12 @@ impl Attachment {
         if progress != 0.0 {
             return;
         }
-        println!("{:?} attachment inserted on player controller", value);
+        println!("{:?} attachment inserted on {}", value, context.entity);
     }
 
     fn on_remove(world: DeferredWorld, context: HookContext) {
         let value = world.get::<Self>(context.entity).unwrap();
-        println!("{:?} attachment removed from player controller", value);
+        println!("{:?} attachment removed from {}", value, context.entity);
     }
 }
 
@@ -78,6 +198,7 @@ pub enum SpecialMove {
     Jumping,
     Diving,
     Climbing { progress: f32, normal: Vec3 },
+    Gliding,
 }
 
 impl SpecialMove {
@@ -92,16 +213,17 @@ impl SpecialMove {
             SpecialMove::Jumping => 0.0,
             SpecialMove::Diving => 0.0,
             SpecialMove::Climbing { progress, .. } => *progress,
+            SpecialMove::Gliding => 0.0,
         };
         if progress != 0.0 {
             return;
         }
-        println!("{:?} special move inserted on player controller", value);
+        println!("{:?} special move inserted on {}", value, context.entity);
     }
 
     fn on_remove(world: DeferredWorld, context: HookContext) {
         let value = world.get::<Self>(context.entity).unwrap();
-        println!("{:?} special move removed from player controller", value);
+        println!("{:?} special move removed from {}", value, context.entity);
     }
 }
 
@@ -123,6 +245,18 @@ pub struct Manoeuvrability {
     pub stop_factor: Scalar,
 }
 
+impl Manoeuvrability {
+    pub fn modified_by(&self, volume: &MovementModifierVolume) -> Self {
+        Self {
+            input_factor: volume.input_factor.apply(self.input_factor),
+            reverse_input_factor: volume.reverse_input_factor.apply(self.reverse_input_factor),
+            jump_factor: volume.jump_factor.apply(self.jump_factor),
+            max_speed_factor: volume.max_speed_factor.apply(self.max_speed_factor),
+            stop_factor: volume.stop_factor.apply(self.stop_factor),
+        }
+    }
+}
+
 #[derive(Reflect)]
 pub struct HitProperties {
     pub normal: Vector,
@@ -149,9 +283,10 @@ impl HitProperties {
 
 #[derive(Component, Default, Reflect)]
 #[reflect(Component, Default)]
-pub struct PlayerHits {
+pub struct CharacterHits {
     ground: Option<HitProperties>,
     water_volume: Option<Entity>,
+    movement_modifier_volume: Option<Entity>,
     water_surface: Option<HitProperties>,
     ledge: Option<HitProperties>,
     wall: Option<HitProperties>,
@@ -162,6 +297,13 @@ pub struct Facing {
     angle: f32,
 }
 
+impl Facing {
+    /// The horizontal direction faced, which is +X at the default angle
+    pub fn direction(&self) -> Vec3 {
+        Vec3::new(self.angle.sin(), 0.0, self.angle.cos())
+    }
+}
+
 impl Default for Facing {
     fn default() -> Self {
         Self {
@@ -170,40 +312,114 @@ impl Default for Facing {
     }
 }
 
-#[derive(Resource)]
-pub struct PlayerAndWaterEntities {
-    pub entities: Vec<Entity>,
+/// Makes jump timing forgiving. A jump pressed slightly too early is buffered until one is
+/// possible, and one pressed slightly too late still takes the jump that was available from
+/// whatever the character just left (coyote time).
+#[derive(Component, Default)]
+pub struct JumpAssist {
+    since_jump_pressed: Option<f32>,
+    coyote_jump: Option<(JumpMode, f32)>,
 }
 
-impl PlayerAndWaterEntities {
-    fn from_entities(entities: &[Entity]) -> Self {
-        Self {
-            entities: entities.iter().copied().collect(),
+/// A character's translation as of the last two fixed steps. The simulation moves the
+/// kinematic body's [`Position`], which Avian writes back to the [`Transform`] after each
+/// physics step. That translation is restored from here before fixed steps run; between
+/// them, the rendered translation is eased from the previous step's to the current step's.
+#[derive(Component, Default)]
+#[component(on_add = Self::on_add)]
+pub struct SimulatedTranslation {
+    previous: Vec3,
+    current: Vec3,
+}
+
+impl SimulatedTranslation {
+    fn on_add(mut world: DeferredWorld, context: HookContext) {
+        let Some(translation) = world
+            .get::<Transform>(context.entity)
+            .map(|transform| transform.translation)
+        else {
+            return;
+        };
+        if let Some(mut simulated) = world.get_mut::<Self>(context.entity) {
+            simulated.teleport(translation);
         }
     }
 
-    fn excluded_entities(&self) -> impl core::iter::IntoIterator<Item = Entity> {
-        self.entities.iter().copied()
+    /// Move without easing from the previous position, such as when spawning
+    pub fn teleport(&mut self, translation: Vec3) {
+        self.previous = translation;
+        self.current = translation;
     }
 }
 
+/// Counts how often a character has started a physics step overlapping something and had to be
+/// pushed out of it. Frequent recoveries point at level geometry that's worth fixing; the last
+/// position and collider are kept to help find it, and all of this shows in the inspector.
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource, Default)]
+pub struct DepenetrationStats {
+    pub recoveries: u32,
+    pub total_distance: Scalar,
+    pub last_position: Vector,
+    pub last_entity: Option<Entity>,
+}
+
+/// What a character is being asked to do, whether by the keyboard for the player, or by AI or
+/// a replay for anything else. Whatever feeds this runs every frame, while the controller reads it
+/// on the fixed timestep; the just-pressed flags are latched until a fixed step has run, so that
+/// presses aren't missed on frames with no fixed step, or repeated on frames with several.
+#[derive(Component, Default)]
+pub struct MovementIntent {
+    // Directional movement, each from -1 to 1; a partly-tilted stick gives less than 1
+    pub input_direction_x: f32,
+    pub input_direction_y: f32,
+
+    // Jump input
+    pub pressing_jump: bool,
+    pub just_pressed_jump: bool,
+
+    // Secondary input
+    pub pressing_secondary: bool,
+    pub just_pressed_secondary: bool,
+}
+
+/// Moves a character (the player or an NPC) according to its [`MovementIntent`]
 #[derive(Component, Reflect, Default)]
 #[reflect(Component, Default)]
 #[require(
-    Player = Player,
-    Collider = PlayerController::collider(0.0),
+    Collider = CharacterController::collider(0.0),
     Facing,
-    CameraFocus = CameraFocus::Active,
-    PlayerHits,
-    RigidBody::Static, // Includes LinearVelocity
+    CharacterHits,
+    MovementIntent,
+    JumpAssist,
+    SimulatedTranslation,
+    RigidBody::Kinematic, // Includes LinearVelocity
     LockedAxes::ROTATION_LOCKED,
     NoTranslationEasing
 )]
-pub struct PlayerController {
+pub struct CharacterController {
     velocity: Vector,
+
+    /// A breakable the secondary button burst through this step, so the roll sweep that
+    /// follows passes through it without breaking it a second time
+    burst_breakable: Option<Entity>,
 }
 
-impl PlayerController {
+/// The character that the keyboard controls and the camera follows
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[require(
+    CharacterController,
+    Player = Player,
+    CameraFocus = CameraFocus::Active
+)]
+pub struct PlayerController;
+
+impl CharacterController {
+    pub fn velocity(&self) -> Vector {
+        self.velocity
+    }
+
     /// Build a collider, reducing by a skin thickness
     pub fn collider(skin_thickness: f32) -> Collider {
         Collider::cylinder(
@@ -223,23 +439,24 @@ impl PlayerController {
 ///
 ///
 /// Notes on Avian:
-/// The LinearVelocity component is manipulated a fair bit by Avian, so this controller uses a
-/// separate component for velocity that's retained across frames.
-/// Furthermore, a non-static RigidBody will have its position updated by Avian in the [SolverSet]
-/// (by calculating [AccumulatedTranslation] as LinearVelocity over time delta, and adding that to the
-/// Position which is later written back to the Translation. All this is avoided here by using a static
-/// RigidBody. See [SolverPlugin] and [IntegratorPlugin].
+/// Each character is a kinematic RigidBody, which Avian moves by its LinearVelocity but which nothing
+/// pushes back on. This controller keeps its own velocity that's retained across frames, and at the
+/// start of each physics step sets the LinearVelocity to whatever reaches the collide-and-slide
+/// result, so the integrated Position lands exactly there. Snapping to surfaces after the step writes
+/// the Position directly, and Avian writes it back to the Transform as for any other body.
 ///
 /// Notes on using Skein:
 /// Be careful which entities have components; a Blender model will have an entity for the object, and
-/// a child entity for the mesh, so the player should have components inserted on the object while a
-/// trimesh should have the Trimesh marker inserted on the mesh.
+/// a child entity for the mesh, so a character should have components inserted on the object while a
+/// trimesh should have the Trimesh marker inserted on the mesh. The player has PlayerController, and
+/// other characters have CharacterController with something feeding their MovementIntent.
 pub struct CharacterControllerPlugin;
 
 impl Plugin for CharacterControllerPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(PlayerAndWaterEntities::from_entities(&[]))
-            .insert_resource(params::CharacterControllerParams::default());
+        app.insert_resource(params::CharacterControllerParams::default())
+            .insert_resource(DepenetrationStats::default())
+            .init_resource::<ActionPrompts>();
         systems::schedule_systems(app);
     }
 }
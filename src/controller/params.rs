@@ -2,6 +2,18 @@ use super::Manoeuvrability;
 use avian3d::math::{Scalar, Vector};
 use bevy::prelude::*;
 
+#[derive(Reflect)]
+pub struct GlidingParams {
+    /// Movement parameters while gliding
+    pub movement: Manoeuvrability,
+
+    /// The proportion of gravity that applies while gliding
+    pub gravity_factor: Scalar,
+
+    /// Terminal velocity while gliding
+    pub terminal_velocity: Scalar,
+}
+
 #[derive(Resource, Reflect)]
 #[reflect(Resource)]
 pub struct CharacterControllerParams {
@@ -32,6 +44,12 @@ pub struct CharacterControllerParams {
     /// Movement parameters while floating on the surface of water
     pub floating_movement: Manoeuvrability,
 
+    /// Movement parameters while clinging to a ledge and shimmying along it
+    pub ledge_cling_movement: Manoeuvrability,
+
+    /// Parameters for gliding while the glide suit power-up is held
+    pub gliding: GlidingParams,
+
     /// The speed above which running occurs
     pub running_speed: Scalar,
 
@@ -50,6 +68,9 @@ pub struct CharacterControllerParams {
     /// Impulse of a wall jump away from the wall's normal
     pub wall_jump_outward_impulse: Scalar,
 
+    /// Impulse into a breakable wall when bursting through it from a wall stick
+    pub burst_through_impulse: Scalar,
+
     /// Initial impulse applied when starting to stream underwater
     pub streaming_impulse: Scalar,
 
@@ -65,6 +86,9 @@ pub struct CharacterControllerParams {
     /// The number of surfaces that collide-and-slide can slide along in a single step
     pub max_collision_bounces: usize,
 
+    /// The number of overlapping surfaces the player can be pushed out of before a single step
+    pub max_depenetration_iterations: usize,
+
     /// The incident dot product above which the player can escape (no ground attachment can occur)
     pub escape_incidence: Scalar,
 
@@ -110,6 +134,9 @@ pub struct CharacterControllerParams {
     /// Time prevented from moving while halting
     pub halting_move_duration: f32,
 
+    /// Time prevented from moving after grabbing a ledge, before clinging to it
+    pub ledge_grab_move_duration: f32,
+
     /// Time prevented from movement while climbing a ledge
     pub climbing_move_duration: f32,
 
@@ -119,6 +146,12 @@ pub struct CharacterControllerParams {
     /// Time to stick to a wall before sliding down
     pub wall_stick_duration: f32,
 
+    /// Time a jump press is remembered for if no jump is possible when it's pressed
+    pub jump_buffer_duration: f32,
+
+    /// Time after leaving the ground, a wall or the water's surface during which its jump still works
+    pub coyote_duration: f32,
+
     /// The vertical speed above which landing will stall the player
     pub landing_stall_speed_threshold: Scalar,
 
@@ -139,6 +172,9 @@ pub struct CharacterControllerParams {
 
     /// The amount to move into a wall to check required space for ledge-grabbing
     pub ledge_grab_required_inset: Scalar,
+
+    /// The distance to search towards the wall when following a ledge's contour
+    pub ledge_cling_wall_probe_distance: Scalar,
 }
 
 impl Default for CharacterControllerParams {
@@ -207,17 +243,37 @@ impl Default for CharacterControllerParams {
                 max_speed_factor: 0.6,
                 stop_factor: 2.0,
             },
+            ledge_cling_movement: Manoeuvrability {
+                input_factor: 0.5,
+                reverse_input_factor: 1.0,
+                jump_factor: 0.0,
+                max_speed_factor: 0.3,
+                stop_factor: 0.1,
+            },
+            gliding: GlidingParams {
+                movement: Manoeuvrability {
+                    input_factor: 1.2,
+                    reverse_input_factor: 1.5,
+                    jump_factor: 0.0,
+                    max_speed_factor: 1.4,
+                    stop_factor: 4.0,
+                },
+                gravity_factor: 0.15,
+                terminal_velocity: -1.5,
+            },
             running_speed: 3.0,
             jumping_gravity_factor: 0.7,
             aerial_roll_gravity_factor: 0.2,
             aerial_roll_progress_rate: 0.5,
             shallow_wall_jump_upward_factor: 0.75,
             wall_jump_outward_impulse: 8.0,
+            burst_through_impulse: 8.0,
             streaming_impulse: 8.0,
             streaming_angular_acceleration: 5.0,
             gravity: Vector::NEG_Y * 9.81 * 1.8,
             collider_skin_thickness: 0.01,
             max_collision_bounces: 3,
+            max_depenetration_iterations: 4,
             escape_incidence: std::f32::consts::FRAC_PI_4,
             buoyant_gravity: Vector::Y * 9.81 * 0.4,
             terminal_velocity: -14.0,
@@ -233,9 +289,12 @@ impl Default for CharacterControllerParams {
             rolling_move_duration: 1.0,
             landing_move_duration: 2.0,
             halting_move_duration: 1.5,
+            ledge_grab_move_duration: 0.3,
             climbing_move_duration: 3.0,
             submersion_move_duration: 1.0,
             wall_stick_duration: 1.5,
+            jump_buffer_duration: 0.12,
+            coyote_duration: 0.1,
             landing_stall_speed_threshold: 3.0,
             landing_roll_speed_threshold: 3.0,
             unroll_speed_threshold: 1.0,
@@ -243,6 +302,7 @@ impl Default for CharacterControllerParams {
             ledge_grab_relative_y: 1.0,
             ledge_grab_tolerance_y: 0.2,
             ledge_grab_required_inset: 0.5,
+            ledge_cling_wall_probe_distance: 0.3,
         }
     }
 }
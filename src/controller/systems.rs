@@ -1,15 +1,19 @@
 use super::{
-    Attachment, Facing, GROUNDING_PROXIMITY, HitProperties, Manoeuvrability, MovementResult,
-    PLAYER_HEIGHT, PlayerAndWaterEntities, PlayerController, PlayerHits, SpecialMove,
+    ActionPrompts, Attachment, CharacterController, CharacterHits, ContextAction,
+    DepenetrationStats, Facing, GROUNDING_PROXIMITY, HitProperties, JumpAssist, JumpMode,
+    Manoeuvrability, MovementIntent, MovementResult, PLAYER_HEIGHT, PLAYER_RADIUS,
+    POWER_UP_PICKUP_PROXIMITY, PlayerController, SimulatedTranslation, SpecialMove, SupportMotion,
     WALL_RETENTION_PROXIMITY,
-    functions::{check_aerial_hit_movement, update_facing},
+    functions::{
+        cast_for_ledge_ground, cast_for_ledge_wall, check_aerial_hit_movement, find_depenetration,
+        shimmy_along_ledge, update_facing,
+    },
     math,
     params::CharacterControllerParams,
 };
 use crate::{
-    InputSystems,
-    input::MovementState,
-    markers::{SpawnPoint, WaterVolume, WaterVolumeExtents},
+    breakable::{BreakableBroken, find_breakable},
+    markers::{Breakable, MovementModifierVolume, PowerUp, SpawnPoint, VolumeExtents, WaterVolume},
     state::AppState,
 };
 
@@ -23,40 +27,49 @@ use avian3d::{
 use bevy::{prelude::*, scene::SceneInstanceReady};
 
 // Known issues:
-// - The character still can intersect a static body (float on water, then push against a sloped bank)
-// - Ledge-grabbing isn't implemented
 // - Jumping with narrow clearance above a slopey peak can yank down to it
 
 pub fn schedule_systems(app: &mut App) {
-    app // Fixed timestep must exceed monitor refresh rate, else shape casts might not run, and problems happen
+    app // Everything runs on the fixed timestep, using inputs latched since the last step
         .insert_resource(Time::<Fixed>::from_hz(96.0))
         .add_observer(on_scene_instance_ready)
         .add_systems(
-            PreUpdate,
+            FixedUpdate,
             (
+                update_markers, // Update player state markers according to current conditions
                 apply_gravity, // Apply vertical accelerations: gravity, buoyancy, and terminal velocity
                 apply_inputs,  // Apply horizontal accelerations and vertical jump impulses
             )
                 .chain()
-                .in_set(InputSystems::ModifyStates)
                 .run_if(in_state(AppState::Game)),
         )
         .add_systems(
             PhysicsSchedule, // Run inside the FixedPostUpdate schedule; note FixedMain runs zero-to-many times before Update
             (
-                move_player, // Apply physics on current velocity, updating position and velocity
-                query_surrounding_hits, // Based on current position, query downward shape cast, and wall-facing shape cast if currently attached
-                hack_position_to_transform,
+                move_player // Collide and slide on current velocity, handing the result to Avian to integrate
+                    .in_set(PhysicsStepSystems::First),
+                query_surrounding_hits // Based on current position, query downward shape cast, and wall-facing shape cast if currently attached
+                    .in_set(PhysicsStepSystems::Last),
             )
-                .chain()
-                .run_if(in_state(AppState::Game))
-                .in_set(PhysicsStepSystems::Last),
+                .run_if(in_state(AppState::Game)),
+        )
+        .add_systems(
+            FixedFirst,
+            restore_simulated_translation.run_if(in_state(AppState::Game)),
+        )
+        .add_systems(
+            FixedLast,
+            (record_simulated_translation, clear_latched_intents).run_if(in_state(AppState::Game)),
+        )
+        .add_systems(
+            RunFixedMainLoop,
+            interpolate_rendered_translation
+                .in_set(RunFixedMainLoopSystems::AfterFixedMainLoop)
+                .run_if(in_state(AppState::Game)),
         )
         .add_systems(
             Update,
-            (
-                update_markers, // Update player state markers according to current conditions
-            )
+            collect_power_ups // Move power-ups the player touches onto the player
                 .run_if(in_state(AppState::Game)),
         );
 
@@ -66,6 +79,9 @@ pub fn schedule_systems(app: &mut App) {
 
 const GROUND_CAST_MAX_HITS: u32 = 3;
 
+/// Length below which a ledge's edge, flattened onto the play plane, is only rounding error
+const LEDGE_EDGE_EPSILON: f32 = 1e-3;
+
 #[derive(PartialEq)]
 enum CommonMarkerUpdates {
     Advance,
@@ -74,33 +90,30 @@ enum CommonMarkerUpdates {
 }
 
 #[derive(PartialEq)]
-enum JumpMode {
+enum SecondaryButtonMode {
     None,
-    Regular {
-        factor: Scalar,
-    },
-    Dive {
-        upward_factor: Scalar,
-        horizontal_factor: Scalar,
-    },
-    Shallow {
-        factor: Scalar,
+    EnterRoll,
+    KickFromWall {
+        normal: Vector,
     },
-    AwayFromNormal {
-        normal: Vec3,
-        upward_impulse_factor: Scalar,
+    BurstThrough {
+        breakable_entity: Entity,
+        normal: Vector,
     },
-    Climb {
-        normal: Vec3,
+    StartStreaming {
+        water_volume_entity: Entity,
     },
 }
 
-#[derive(PartialEq)]
-enum SecondaryButtonMode {
-    None,
-    EnterRoll,
-    KickFromWall { normal: Vector },
-    StartStreaming { water_volume_entity: Entity },
+impl SecondaryButtonMode {
+    fn context_action(&self) -> Option<ContextAction> {
+        match self {
+            Self::None => None,
+            Self::EnterRoll | Self::BurstThrough { .. } => Some(ContextAction::Roll),
+            Self::KickFromWall { .. } => Some(ContextAction::Kick),
+            Self::StartStreaming { .. } => Some(ContextAction::Stream),
+        }
+    }
 }
 
 enum ManoeuvreMode<'a> {
@@ -112,805 +125,1092 @@ enum ManoeuvreMode<'a> {
         normal: Vector,
     },
     RadialMovement(&'a Manoeuvrability),
+    AlongLedge {
+        manoeuvrability: &'a Manoeuvrability,
+        normal: Vector,
+        ledge_normal: Vector,
+    },
+}
+
+impl<'a> ManoeuvreMode<'a> {
+    fn manoeuvrability(&self) -> Option<&'a Manoeuvrability> {
+        match self {
+            Self::Freeze | Self::Freewheel => None,
+            Self::HorizontalInput(manoeuvrability) => Some(*manoeuvrability),
+            Self::PlanarInput {
+                manoeuvrability, ..
+            } => Some(*manoeuvrability),
+            Self::RadialMovement(manoeuvrability) => Some(*manoeuvrability),
+            Self::AlongLedge {
+                manoeuvrability, ..
+            } => Some(*manoeuvrability),
+        }
+    }
+
+    fn with_manoeuvrability<'b>(self, manoeuvrability: &'b Manoeuvrability) -> ManoeuvreMode<'b> {
+        match self {
+            Self::Freeze => ManoeuvreMode::Freeze,
+            Self::Freewheel => ManoeuvreMode::Freewheel,
+            Self::HorizontalInput(_) => ManoeuvreMode::HorizontalInput(manoeuvrability),
+            Self::PlanarInput { normal, .. } => ManoeuvreMode::PlanarInput {
+                manoeuvrability,
+                normal,
+            },
+            Self::RadialMovement(_) => ManoeuvreMode::RadialMovement(manoeuvrability),
+            Self::AlongLedge {
+                normal,
+                ledge_normal,
+                ..
+            } => ManoeuvreMode::AlongLedge {
+                manoeuvrability,
+                normal,
+                ledge_normal,
+            },
+        }
+    }
 }
 
 fn on_scene_instance_ready(
     _on: On<SceneInstanceReady>,
-    mut commands: Commands,
     mut player_query: Query<
-        (Entity, &mut Transform),
+        (&mut Transform, &mut SimulatedTranslation),
         (With<PlayerController>, Without<SpawnPoint>),
     >,
-    water_query: Query<Entity, With<WaterVolume>>,
     spawn_points: Query<&Transform, (With<SpawnPoint>, Without<PlayerController>)>,
 ) -> Result<(), BevyError> {
-    let (player_entity, mut player_transform) = player_query.single_mut()?;
-    let mut excluded_entities: Vec<Entity> = water_query.iter().collect();
+    let (mut player_transform, mut simulated) = player_query.single_mut()?;
     let spawn_transform = spawn_points.single()?;
-    excluded_entities.push(player_entity);
-    commands.insert_resource(PlayerAndWaterEntities::from_entities(&excluded_entities));
     player_transform.translation = spawn_transform.translation;
+    simulated.teleport(spawn_transform.translation);
     Ok(())
 }
 
 #[cfg(debug_assertions)]
 fn respawn_on_debug_press(
     _: On<DebugPressed>,
-    mut player_query: Query<&mut Transform, (With<PlayerController>, Without<SpawnPoint>)>,
+    mut player_query: Query<
+        (&mut Transform, &mut SimulatedTranslation),
+        (With<PlayerController>, Without<SpawnPoint>),
+    >,
     spawn_points: Query<&Transform, (With<SpawnPoint>, Without<PlayerController>)>,
 ) -> Result<(), BevyError> {
-    let mut player_transform = player_query.single_mut()?;
+    let (mut player_transform, mut simulated) = player_query.single_mut()?;
     let spawn_transform = spawn_points.single()?;
     player_transform.translation = spawn_transform.translation;
+    simulated.teleport(spawn_transform.translation);
     Ok(())
 }
 
 fn query_surrounding_hits(
-    mut player_query: Query<
+    mut character_query: Query<
         (
             Entity,
-            &PlayerController,
-            &mut PlayerHits,
-            &mut Transform,
+            &CharacterController,
+            &mut CharacterHits,
+            &mut Position,
             &Rotation,
-            Option<&Attachment>,
+            Option<&mut Attachment>,
         ),
-        With<PlayerController>,
+        With<CharacterController>,
+    >,
+    water_query: Query<
+        (Entity, &GlobalTransform, &VolumeExtents),
+        (With<WaterVolume>, Without<CharacterController>),
+    >,
+    modifier_query: Query<
+        (Entity, &GlobalTransform, &VolumeExtents),
+        (With<MovementModifierVolume>, Without<CharacterController>),
     >,
-    water_query: Query<(Entity, &GlobalTransform, &WaterVolumeExtents), Without<PlayerController>>,
     spatial_queries: Res<SpatialQueryPipeline>,
     params: Res<CharacterControllerParams>,
 ) -> Result<(), BevyError> {
-    let Ok((entity, controller, mut hits, mut transform, rotation, attachment)) =
-        player_query.single_mut()
-    else {
-        println!("Not running query_surrounding_hits this timestep");
-        return Ok(());
-    };
+    for (entity, controller, mut hits, mut position, rotation, mut attachment) in
+        character_query.iter_mut()
+    {
+        // Cast from the player's position downwards
+        let shape = CharacterController::collider(params.collider_skin_thickness);
+        let shape_rotation = Quaternion::default();
+        let direction = Dir3::NEG_Y;
+        let internal_cast_distance = 0.5 * PLAYER_HEIGHT;
+        let external_cast_distance = GROUNDING_PROXIMITY;
+        let config = ShapeCastConfig {
+            max_distance: internal_cast_distance + external_cast_distance,
+            ..default()
+        };
+        let filter = SpatialQueryFilter::default().with_excluded_entities([entity]);
+        let centre_cast_hits = spatial_queries.shape_hits(
+            &shape,
+            position.0 + (internal_cast_distance * Vec3::Y),
+            shape_rotation,
+            direction,
+            GROUND_CAST_MAX_HITS,
+            &config,
+            &filter,
+        );
 
-    // Cast from the player's position downwards
-    let shape = PlayerController::collider(params.collider_skin_thickness);
-    let shape_rotation = Quaternion::default();
-    let direction = Dir3::NEG_Y;
-    let internal_cast_distance = 0.5 * PLAYER_HEIGHT;
-    let external_cast_distance = GROUNDING_PROXIMITY;
-    let config = ShapeCastConfig {
-        max_distance: internal_cast_distance + external_cast_distance,
-        ..default()
-    };
-    let filter = SpatialQueryFilter::default().with_excluded_entities([entity]);
-    let centre_cast_hits = spatial_queries.shape_hits(
-        &shape,
-        transform.translation + (internal_cast_distance * Vec3::Y),
-        shape_rotation,
-        direction,
-        GROUND_CAST_MAX_HITS,
-        &config,
-        &filter,
-    );
+        // Record hits so long as the player is not moving somewhat away from the surface
+        hits.ground = centre_cast_hits
+            .iter()
+            .filter(|hit| {
+                !water_query
+                    .iter()
+                    .any(|(entity, _, _)| entity == hit.entity)
+            })
+            .filter(|hit| hit.distance >= internal_cast_distance)
+            .filter(|hit| hit.normal1.dot(controller.velocity) < params.escape_incidence)
+            .next()
+            .map(|hit| HitProperties::from_avian_hit(hit, rotation, internal_cast_distance));
+        hits.water_surface = centre_cast_hits
+            .iter()
+            .filter(|hit| {
+                water_query
+                    .iter()
+                    .any(|(entity, _, _)| entity == hit.entity)
+            })
+            .next()
+            .map(|hit| HitProperties::from_avian_hit(hit, rotation, 0.0));
+        hits.water_volume = find_containing_volume(
+            &position.0,
+            water_query.iter(),
+            params.collider_skin_thickness,
+        );
+        hits.movement_modifier_volume = find_containing_volume(
+            &position.0,
+            modifier_query.iter(),
+            params.collider_skin_thickness,
+        );
 
-    // Record hits so long as the player is not moving somewhat away from the surface
-    hits.ground = centre_cast_hits
-        .iter()
-        .filter(|hit| {
-            !water_query
-                .iter()
-                .any(|(entity, _, _)| entity == hit.entity)
-        })
-        .filter(|hit| hit.distance >= internal_cast_distance)
-        .filter(|hit| hit.normal1.dot(controller.velocity) < params.escape_incidence)
-        .next()
-        .map(|hit| HitProperties::from_avian_hit(hit, rotation, internal_cast_distance));
-    hits.water_surface = centre_cast_hits
-        .iter()
-        .filter(|hit| {
-            water_query
-                .iter()
-                .any(|(entity, _, _)| entity == hit.entity)
-        })
-        .next()
-        .map(|hit| HitProperties::from_avian_hit(hit, rotation, 0.0));
-    hits.water_volume = water_query
-        .iter()
-        .filter(|(_, water_transform, water_volume)| {
-            let water_translation = water_transform.translation();
-            if water_transform.translation() == Vec3::ZERO {
-                panic!("A water volume has a zero origin. This is probably not what you want.");
+        // Keep track of what's underfoot, such as when stepping from the terrain onto a platform
+        if let (
+            Some(Attachment::Grounded {
+                supporting_entity, ..
+            }),
+            Some(hit),
+        ) = (attachment.as_deref_mut(), &hits.ground)
+        {
+            *supporting_entity = hit.entity;
+        }
+
+        // Some further updates: assign wall and ledge hits, and snap to surfaces
+        hits.ledge = None;
+        match attachment.as_deref() {
+            Some(Attachment::Grounded { .. }) => {
+                if let Some(hit) = &hits.ground {
+                    position.0 +=
+                        (hit.distance - params.collider_skin_thickness) * direction.as_vec3();
+                }
+                hits.wall = None;
             }
-            math::inside_volume(
-                &transform.translation,
-                &water_translation,
-                water_volume,
-                params.collider_skin_thickness,
-            )
-        })
-        .next()
-        .map(|(entity, _, _)| entity);
-
-    // Some further updates: assign wall hit, and snap to surfaces
-    match attachment {
-        Some(Attachment::Grounded { .. }) => {
-            if let Some(hit) = &hits.ground {
-                transform.translation +=
-                    (hit.distance - params.collider_skin_thickness) * direction.as_vec3();
+            Some(Attachment::LedgeGrabbed { normal }) => {
+                let solid_filter = SpatialQueryFilter::default().with_excluded_entities(
+                    water_query
+                        .iter()
+                        .map(|(entity, _, _)| entity)
+                        .chain([entity]),
+                );
+                hits.wall = cast_for_ledge_wall(
+                    &spatial_queries,
+                    &solid_filter,
+                    &params,
+                    &position.0,
+                    normal,
+                    WALL_RETENTION_PROXIMITY,
+                )
+                .map(|hit| HitProperties::from_avian_hit(&hit, rotation, 0.0));
+                hits.ledge = cast_for_ledge_ground(
+                    &spatial_queries,
+                    &solid_filter,
+                    &params,
+                    &position.0,
+                    normal,
+                )
+                .map(|hit| HitProperties::from_avian_hit(&hit, rotation, 0.0));
             }
-            hits.wall = None;
-        }
-        Some(Attachment::Walled { normal, .. }) => {
-            let direction = Dir3::new(-normal)?;
-            let config = ShapeCastConfig {
-                max_distance: WALL_RETENTION_PROXIMITY,
-                ..default()
-            };
-            let shape_hit = spatial_queries.cast_shape(
-                &shape,
-                transform.translation,
-                shape_rotation,
-                direction,
-                &config,
-                &filter,
-            );
-            hits.wall = shape_hit.map(|hit| HitProperties::from_avian_hit(&hit, rotation, 0.0));
-        }
-        Some(Attachment::Floating { .. }) => {
-            if let Some(hit) = &hits.water_surface {
-                transform.translation +=
-                    (hit.distance - params.collider_skin_thickness) * direction.as_vec3();
+            Some(Attachment::Walled { normal, .. }) => {
+                let direction = Dir3::new(-normal)?;
+                let config = ShapeCastConfig {
+                    max_distance: WALL_RETENTION_PROXIMITY,
+                    ..default()
+                };
+                let shape_hit = spatial_queries.cast_shape(
+                    &shape,
+                    position.0,
+                    shape_rotation,
+                    direction,
+                    &config,
+                    &filter,
+                );
+                hits.wall = shape_hit.map(|hit| HitProperties::from_avian_hit(&hit, rotation, 0.0));
+            }
+            Some(Attachment::Floating {
+                water_volume_entity,
+            }) => {
+                // Follow the surface as it rises and falls with any tide, but come to rest on
+                // ground beneath it so that a receding tide leaves the player stranded
+                if let Ok((_, water_transform, water_volume)) =
+                    water_query.get(*water_volume_entity)
+                {
+                    let surface_y = water_transform.translation().y + water_volume.half_extent_y;
+                    position.0.y = match &hits.ground {
+                        Some(hit) => surface_y
+                            .max(position.0.y - (hit.distance - params.collider_skin_thickness)),
+                        None => surface_y,
+                    };
+                }
+            }
+            _ => {
+                hits.wall = None;
             }
-        }
-        _ => {
-            hits.wall = None;
         }
     }
-
     Ok(())
 }
 
-/// Hack: Replace Avian's Position and Rotation components with the updated Transform's translation
-/// and rotation just before the [`PhysicsTransformPlugin`] writes them back to the Transform.
-/// Not sure how to control this properly so that the writeback doesn't occur for the player entity.
-/// I wonder if [`ApplyPosToTransform`] can be not applied to the player?
-fn hack_position_to_transform(
-    mut player_query: Query<(&Transform, &mut Position, &mut Rotation), With<PlayerController>>,
-) -> Result<(), BevyError> {
-    let Ok((transform, mut position, mut rotation)) = player_query.single_mut() else {
-        println!("Not running hack_position_to_transform this timestep");
-        return Ok(());
-    };
-    position.0 = transform.translation;
-    rotation.0 = transform.rotation;
-    Ok(())
+/// Find the first of the given volumes which contains the point
+fn find_containing_volume<'a>(
+    point: &Vec3,
+    volumes: impl Iterator<Item = (Entity, &'a GlobalTransform, &'a VolumeExtents)>,
+    skin_thickness: f32,
+) -> Option<Entity> {
+    volumes
+        .filter(|(_, volume_transform, volume)| {
+            let volume_translation = volume_transform.translation();
+            if volume_translation == Vec3::ZERO {
+                panic!("A volume has a zero origin. This is probably not what you want.");
+            }
+            math::inside_volume(point, &volume_translation, volume, skin_thickness)
+        })
+        .next()
+        .map(|(entity, _, _)| entity)
+}
+
+/// Put the player back where the simulation left it, undoing any easing for rendering
+fn restore_simulated_translation(
+    mut player_query: Query<(&mut Transform, &SimulatedTranslation), With<CharacterController>>,
+) {
+    for (mut transform, simulated) in player_query.iter_mut() {
+        transform.translation = simulated.current;
+    }
+}
+
+/// Keep where the player was after this fixed step, and the one before
+fn record_simulated_translation(
+    mut player_query: Query<(&Transform, &mut SimulatedTranslation), With<CharacterController>>,
+) {
+    for (transform, mut simulated) in player_query.iter_mut() {
+        simulated.previous = simulated.current;
+        simulated.current = transform.translation;
+    }
+}
+
+/// Ease the rendered translation between the last two fixed steps, by how far the frame's
+/// time has run past the latest one. Anything following the player, such as the camera,
+/// then moves smoothly too.
+fn interpolate_rendered_translation(
+    mut player_query: Query<(&mut Transform, &SimulatedTranslation), With<CharacterController>>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    let overstep_fraction = fixed_time.overstep_fraction();
+    for (mut transform, simulated) in player_query.iter_mut() {
+        transform.translation = simulated
+            .previous
+            .lerp(simulated.current, overstep_fraction);
+    }
 }
 
-/// Update the [`Attachment`] and [`SpecialMove`] components for the [`PlayerController`]
+/// Presses have been seen by a fixed step once it has run, so stop reporting them
+fn clear_latched_intents(mut intent_query: Query<&mut MovementIntent>) {
+    for mut intent in intent_query.iter_mut() {
+        intent.just_pressed_jump = false;
+        intent.just_pressed_secondary = false;
+    }
+}
+
+/// Update the [`Attachment`] and [`SpecialMove`] components for the [`CharacterController`]
 fn update_markers(
     mut commands: Commands,
-    mut player_query: Query<(
+    mut character_query: Query<(
         Entity,
-        &PlayerHits,
-        &PlayerController,
+        &CharacterHits,
+        &CharacterController,
+        &mut Position,
         &mut Transform,
         Option<&Attachment>,
         Option<&SpecialMove>,
+        Option<&PowerUp>,
+        &MovementIntent,
     )>,
-    inputs: Res<MovementState>,
     params: Res<CharacterControllerParams>,
     time: Res<Time>,
 ) -> Result<(), BevyError> {
-    let Ok((entity, hits, controller, mut transform, attachment, special_move)) =
-        player_query.single_mut()
-    else {
-        println!("Not running update_markers this timestep");
-        return Ok(());
-    };
-    let vertical_speed = controller.velocity.dot(Vector::Y);
-    let horizontal_speed = controller.velocity.dot(
-        controller
-            .velocity
-            .with_y(0.0)
-            .try_normalize()
-            .unwrap_or(Vector::X),
-    );
-    let input_opposes_motion = inputs.input_direction_x != 0.0
-        && controller
-            .velocity
-            .dot(Vec3::new(inputs.input_direction_x, 0.0, 0.0))
-            < 0.0;
-    let grounding = match &hits.ground {
-        Some(hit) if hit.normal_angle <= params.max_sliding_slope_angle => Some(hit.normal),
-        _ => None,
-    };
-    let slope_is_walkable = match &hits.ground {
-        Some(hit) if hit.normal_angle <= params.max_walking_slope_angle => true,
-        _ => false,
-    };
-    let above_running_speed = horizontal_speed > params.running_speed;
-    let is_landing_hard =
-        grounding.is_some() && vertical_speed < -params.landing_stall_speed_threshold;
-    let is_landing_fast =
-        grounding.is_some() && horizontal_speed > params.landing_roll_speed_threshold;
-    let is_on_wall = hits.wall.is_some();
-    let on_water_surface_entity = hits.water_surface.as_ref().map(|hit| hit.entity);
-    let in_water_volume = hits.water_volume;
-
-    let next_step: CommonMarkerUpdates = match attachment {
-        None => match special_move {
-            None => {
-                // Falling
-                if let Some(normal) = grounding {
-                    if !slope_is_walkable {
-                        commands
-                            .entity(entity)
-                            .insert((Attachment::Grounded { normal }, SpecialMove::Sliding));
-                    } else if is_landing_fast {
+    for (
+        entity,
+        hits,
+        controller,
+        mut position,
+        mut transform,
+        attachment,
+        special_move,
+        power_up,
+        inputs,
+    ) in character_query.iter_mut()
+    {
+        let vertical_speed = controller.velocity.dot(Vector::Y);
+        let horizontal_speed = controller.velocity.dot(
+            controller
+                .velocity
+                .with_y(0.0)
+                .try_normalize()
+                .unwrap_or(Vector::X),
+        );
+        let input_opposes_motion = inputs.input_direction_x != 0.0
+            && controller
+                .velocity
+                .dot(Vec3::new(inputs.input_direction_x, 0.0, 0.0))
+                < 0.0;
+        let grounding = match &hits.ground {
+            Some(hit) if hit.normal_angle <= params.max_sliding_slope_angle => {
+                Some((hit.normal, hit.entity))
+            }
+            _ => None,
+        };
+        let slope_is_walkable = match &hits.ground {
+            Some(hit) if hit.normal_angle <= params.max_walking_slope_angle => true,
+            _ => false,
+        };
+        let above_running_speed = horizontal_speed > params.running_speed;
+        let is_landing_hard =
+            grounding.is_some() && vertical_speed < -params.landing_stall_speed_threshold;
+        let is_landing_fast =
+            grounding.is_some() && horizontal_speed > params.landing_roll_speed_threshold;
+        let is_on_wall = hits.wall.is_some();
+        let is_holding_ledge = is_on_wall && hits.ledge.is_some();
+        let has_glide_suit = power_up == Some(&PowerUp::GlideSuit);
+        let wants_to_glide = has_glide_suit && inputs.pressing_jump && vertical_speed < 0.0;
+        let on_water_surface_entity = hits.water_surface.as_ref().map(|hit| hit.entity);
+        let in_water_volume = hits.water_volume;
+
+        let next_step: CommonMarkerUpdates = match attachment {
+            None => match special_move {
+                None => {
+                    // Falling
+                    if let Some((normal, supporting_entity)) = grounding {
+                        if !slope_is_walkable {
+                            commands.entity(entity).insert((
+                                Attachment::Grounded {
+                                    normal,
+                                    supporting_entity,
+                                },
+                                SpecialMove::Sliding,
+                            ));
+                        } else if is_landing_fast {
+                            commands.entity(entity).insert((
+                                Attachment::Grounded {
+                                    normal,
+                                    supporting_entity,
+                                },
+                                SpecialMove::Rolling { progress: 0.0 },
+                            ));
+                        } else if is_landing_hard {
+                            commands.entity(entity).insert((
+                                Attachment::Grounded {
+                                    normal,
+                                    supporting_entity,
+                                },
+                                SpecialMove::Landing { progress: 0.0 },
+                            ));
+                        } else {
+                            commands.entity(entity).insert(Attachment::Grounded {
+                                normal,
+                                supporting_entity,
+                            });
+                        }
+                        CommonMarkerUpdates::None
+                    } else if let Some(water_volume_entity) = in_water_volume {
                         commands.entity(entity).insert((
-                            Attachment::Grounded { normal },
-                            SpecialMove::Rolling { progress: 0.0 },
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
                         ));
-                    } else if is_landing_hard {
+                        CommonMarkerUpdates::None
+                    } else if wants_to_glide {
+                        commands.entity(entity).insert(SpecialMove::Gliding);
+                        CommonMarkerUpdates::None
+                    } else {
+                        CommonMarkerUpdates::Advance
+                    }
+                }
+                Some(SpecialMove::Landing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Rolling { .. }) => {
+                    // Rolled off of ground
+                    if horizontal_speed < params.unroll_speed_threshold {
+                        commands.entity(entity).remove::<SpecialMove>();
+                        CommonMarkerUpdates::None
+                    } else {
+                        // Unroll after extended delay while in the air
+                        CommonMarkerUpdates::AdvanceRate(params.aerial_roll_progress_rate)
+                    }
+                }
+                Some(SpecialMove::Running) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Halting { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Sliding) => {
+                    // Falling while in a slide move
+                    if let Some((normal, supporting_entity)) = grounding {
+                        commands.entity(entity).insert(Attachment::Grounded {
+                            normal,
+                            supporting_entity,
+                        });
+                        CommonMarkerUpdates::None
+                    } else if let Some(water_volume_entity) = in_water_volume {
                         commands.entity(entity).insert((
-                            Attachment::Grounded { normal },
-                            SpecialMove::Landing { progress: 0.0 },
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
                         ));
+                        CommonMarkerUpdates::None
                     } else {
-                        commands
-                            .entity(entity)
-                            .insert(Attachment::Grounded { normal });
+                        CommonMarkerUpdates::Advance
                     }
-                    CommonMarkerUpdates::None
-                } else if let Some(water_volume_entity) = in_water_volume {
-                    commands.entity(entity).insert((
-                        Attachment::Submerged {
-                            water_volume_entity,
-                        },
-                        SpecialMove::Halting { progress: 0.0 },
-                    ));
-                    CommonMarkerUpdates::None
-                } else {
-                    CommonMarkerUpdates::Advance
                 }
-            }
-            Some(SpecialMove::Landing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Rolling { .. }) => {
-                // Rolled off of ground
-                if horizontal_speed < params.unroll_speed_threshold {
-                    commands.entity(entity).remove::<SpecialMove>();
-                    CommonMarkerUpdates::None
-                } else {
-                    // Unroll after extended delay while in the air
-                    CommonMarkerUpdates::AdvanceRate(params.aerial_roll_progress_rate)
+                Some(SpecialMove::Jumping { .. }) => {
+                    // Jumping upward
+                    if let Some((normal, supporting_entity)) = grounding {
+                        if !slope_is_walkable {
+                            commands.entity(entity).insert((
+                                Attachment::Grounded {
+                                    normal,
+                                    supporting_entity,
+                                },
+                                SpecialMove::Sliding,
+                            ));
+                        } else if is_landing_fast {
+                            commands.entity(entity).insert((
+                                Attachment::Grounded {
+                                    normal,
+                                    supporting_entity,
+                                },
+                                SpecialMove::Rolling { progress: 0.0 },
+                            ));
+                        } else if is_landing_hard {
+                            commands.entity(entity).insert((
+                                Attachment::Grounded {
+                                    normal,
+                                    supporting_entity,
+                                },
+                                SpecialMove::Landing { progress: 0.0 },
+                            ));
+                        } else {
+                            commands.entity(entity).insert(Attachment::Grounded {
+                                normal,
+                                supporting_entity,
+                            });
+                        }
+                        CommonMarkerUpdates::None
+                    } else if let Some(water_volume_entity) = in_water_volume {
+                        commands.entity(entity).insert((
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
+                        ));
+                        CommonMarkerUpdates::None
+                    } else if wants_to_glide {
+                        commands.entity(entity).insert(SpecialMove::Gliding);
+                        CommonMarkerUpdates::None
+                    } else if !inputs.pressing_jump || vertical_speed < 0.0 {
+                        commands.entity(entity).remove::<SpecialMove>();
+                        CommonMarkerUpdates::None
+                    } else {
+                        CommonMarkerUpdates::Advance
+                    }
                 }
-            }
-            Some(SpecialMove::Running) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Halting { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Sliding) => {
-                // Falling while in a slide move
-                if let Some(normal) = grounding {
-                    commands
-                        .entity(entity)
-                        .insert(Attachment::Grounded { normal });
-                    CommonMarkerUpdates::None
-                } else if let Some(water_volume_entity) = in_water_volume {
-                    commands.entity(entity).insert((
-                        Attachment::Submerged {
-                            water_volume_entity,
-                        },
-                        SpecialMove::Halting { progress: 0.0 },
-                    ));
-                    CommonMarkerUpdates::None
-                } else {
-                    CommonMarkerUpdates::Advance
+                Some(SpecialMove::Diving) => {
+                    // Diving through air
+                    if let Some((normal, supporting_entity)) = grounding {
+                        if !slope_is_walkable {
+                            commands.entity(entity).insert((
+                                Attachment::Grounded {
+                                    normal,
+                                    supporting_entity,
+                                },
+                                SpecialMove::Sliding,
+                            ));
+                        } else if is_landing_fast {
+                            commands.entity(entity).insert((
+                                Attachment::Grounded {
+                                    normal,
+                                    supporting_entity,
+                                },
+                                SpecialMove::Rolling { progress: 0.0 },
+                            ));
+                        } else if is_landing_hard {
+                            commands.entity(entity).insert((
+                                Attachment::Grounded {
+                                    normal,
+                                    supporting_entity,
+                                },
+                                SpecialMove::Landing { progress: 0.0 },
+                            ));
+                        } else {
+                            commands.entity(entity).insert(Attachment::Grounded {
+                                normal,
+                                supporting_entity,
+                            });
+                            commands.entity(entity).remove::<SpecialMove>();
+                        }
+                        CommonMarkerUpdates::None
+                    } else if let Some(water_volume_entity) = in_water_volume {
+                        commands.entity(entity).insert((
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
+                        ));
+                        CommonMarkerUpdates::None
+                    } else {
+                        CommonMarkerUpdates::Advance
+                    }
                 }
-            }
-            Some(SpecialMove::Jumping { .. }) => {
-                // Jumping upward
-                if let Some(normal) = grounding {
-                    if !slope_is_walkable {
-                        commands
-                            .entity(entity)
-                            .insert((Attachment::Grounded { normal }, SpecialMove::Sliding));
-                    } else if is_landing_fast {
+                Some(SpecialMove::Climbing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Gliding) => {
+                    // Gliding through air with the glide suit
+                    if let Some((normal, supporting_entity)) = grounding {
+                        if !slope_is_walkable {
+                            commands.entity(entity).insert((
+                                Attachment::Grounded {
+                                    normal,
+                                    supporting_entity,
+                                },
+                                SpecialMove::Sliding,
+                            ));
+                        } else if is_landing_fast {
+                            commands.entity(entity).insert((
+                                Attachment::Grounded {
+                                    normal,
+                                    supporting_entity,
+                                },
+                                SpecialMove::Rolling { progress: 0.0 },
+                            ));
+                        } else {
+                            commands.entity(entity).insert(Attachment::Grounded {
+                                normal,
+                                supporting_entity,
+                            });
+                            commands.entity(entity).remove::<SpecialMove>();
+                        }
+                        CommonMarkerUpdates::None
+                    } else if let Some(water_volume_entity) = in_water_volume {
                         commands.entity(entity).insert((
-                            Attachment::Grounded { normal },
-                            SpecialMove::Rolling { progress: 0.0 },
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
                         ));
-                    } else if is_landing_hard {
+                        CommonMarkerUpdates::None
+                    } else if !has_glide_suit || !inputs.pressing_jump {
+                        commands.entity(entity).remove::<SpecialMove>();
+                        CommonMarkerUpdates::None
+                    } else {
+                        CommonMarkerUpdates::Advance
+                    }
+                }
+            },
+            Some(Attachment::Grounded { .. }) => match special_move {
+                None => {
+                    // Standing, Walking
+                    if let Some(water_volume_entity) = in_water_volume {
                         commands.entity(entity).insert((
-                            Attachment::Grounded { normal },
-                            SpecialMove::Landing { progress: 0.0 },
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
                         ));
+                        CommonMarkerUpdates::None
+                    } else if grounding.is_none() {
+                        commands.entity(entity).remove::<Attachment>();
+                        CommonMarkerUpdates::None
+                    } else if !slope_is_walkable {
+                        commands.entity(entity).insert(SpecialMove::Sliding);
+                        CommonMarkerUpdates::None
+                    } else if above_running_speed {
+                        commands.entity(entity).insert(SpecialMove::Running);
+                        CommonMarkerUpdates::None
                     } else {
-                        commands
-                            .entity(entity)
-                            .insert(Attachment::Grounded { normal });
+                        CommonMarkerUpdates::Advance
                     }
-                    CommonMarkerUpdates::None
-                } else if let Some(water_volume_entity) = in_water_volume {
-                    commands.entity(entity).insert((
-                        Attachment::Submerged {
-                            water_volume_entity,
-                        },
-                        SpecialMove::Halting { progress: 0.0 },
-                    ));
-                    CommonMarkerUpdates::None
-                } else if !inputs.pressing_jump || vertical_speed < 0.0 {
-                    commands.entity(entity).remove::<SpecialMove>();
-                    CommonMarkerUpdates::None
-                } else {
-                    CommonMarkerUpdates::Advance
                 }
-            }
-            Some(SpecialMove::Diving) => {
-                // Diving through air
-                if let Some(normal) = grounding {
-                    if !slope_is_walkable {
-                        commands
-                            .entity(entity)
-                            .insert((Attachment::Grounded { normal }, SpecialMove::Sliding));
-                    } else if is_landing_fast {
+                Some(SpecialMove::Landing { .. }) => {
+                    // Stationary after landing hard
+                    if let Some(water_volume_entity) = in_water_volume {
                         commands.entity(entity).insert((
-                            Attachment::Grounded { normal },
-                            SpecialMove::Rolling { progress: 0.0 },
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
                         ));
-                    } else if is_landing_hard {
+                        CommonMarkerUpdates::None
+                    } else if grounding.is_none() {
+                        commands.entity(entity).remove::<Attachment>();
+                        CommonMarkerUpdates::None
+                    } else if !slope_is_walkable {
+                        commands.entity(entity).insert(SpecialMove::Sliding);
+                        CommonMarkerUpdates::None
+                    } else {
+                        CommonMarkerUpdates::Advance
+                    }
+                }
+                Some(SpecialMove::Rolling { .. }) => {
+                    // Landed fast, or pressed secondary button while running
+                    if let Some(water_volume_entity) = in_water_volume {
                         commands.entity(entity).insert((
-                            Attachment::Grounded { normal },
-                            SpecialMove::Landing { progress: 0.0 },
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
                         ));
+                        CommonMarkerUpdates::None
+                    } else if grounding.is_none() {
+                        commands.entity(entity).remove::<Attachment>();
+                        CommonMarkerUpdates::None
+                    } else if horizontal_speed < params.unroll_speed_threshold {
+                        commands.entity(entity).remove::<SpecialMove>();
+                        CommonMarkerUpdates::None
                     } else {
+                        CommonMarkerUpdates::Advance
+                    }
+                }
+                Some(SpecialMove::Running) => {
+                    // Running on ground
+                    if let Some(water_volume_entity) = in_water_volume {
+                        commands.entity(entity).insert((
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
+                        ));
+                        CommonMarkerUpdates::None
+                    } else if grounding.is_none() {
+                        commands.entity(entity).remove::<Attachment>();
+                        commands.entity(entity).remove::<SpecialMove>();
+                        CommonMarkerUpdates::None
+                    } else if !slope_is_walkable {
+                        commands.entity(entity).insert(SpecialMove::Sliding);
+                        CommonMarkerUpdates::None
+                    } else if !above_running_speed {
+                        commands.entity(entity).remove::<SpecialMove>();
+                        CommonMarkerUpdates::None
+                    } else if input_opposes_motion {
                         commands
                             .entity(entity)
-                            .insert(Attachment::Grounded { normal });
-                        commands.entity(entity).remove::<SpecialMove>();
+                            .insert(SpecialMove::Halting { progress: 0.0 });
+                        CommonMarkerUpdates::None
+                    } else {
+                        CommonMarkerUpdates::Advance
                     }
-                    CommonMarkerUpdates::None
-                } else if let Some(water_volume_entity) = in_water_volume {
-                    commands.entity(entity).insert((
-                        Attachment::Submerged {
-                            water_volume_entity,
-                        },
-                        SpecialMove::Halting { progress: 0.0 },
-                    ));
-                    CommonMarkerUpdates::None
-                } else {
-                    CommonMarkerUpdates::Advance
                 }
-            }
-            Some(SpecialMove::Climbing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-        },
-        Some(Attachment::Grounded { .. }) => match special_move {
-            None => {
-                // Standing, Walking
-                if let Some(water_volume_entity) = in_water_volume {
-                    commands.entity(entity).insert((
-                        Attachment::Submerged {
-                            water_volume_entity,
-                        },
-                        SpecialMove::Halting { progress: 0.0 },
-                    ));
-                    CommonMarkerUpdates::None
-                } else if grounding.is_none() {
-                    commands.entity(entity).remove::<Attachment>();
-                    CommonMarkerUpdates::None
-                } else if !slope_is_walkable {
-                    commands.entity(entity).insert(SpecialMove::Sliding);
-                    CommonMarkerUpdates::None
-                } else if above_running_speed {
-                    commands.entity(entity).insert(SpecialMove::Running);
-                    CommonMarkerUpdates::None
-                } else {
-                    CommonMarkerUpdates::Advance
-                }
-            }
-            Some(SpecialMove::Landing { .. }) => {
-                // Stationary after landing hard
-                if let Some(water_volume_entity) = in_water_volume {
-                    commands.entity(entity).insert((
-                        Attachment::Submerged {
-                            water_volume_entity,
-                        },
-                        SpecialMove::Halting { progress: 0.0 },
-                    ));
-                    CommonMarkerUpdates::None
-                } else if grounding.is_none() {
-                    commands.entity(entity).remove::<Attachment>();
-                    CommonMarkerUpdates::None
-                } else if !slope_is_walkable {
-                    commands.entity(entity).insert(SpecialMove::Sliding);
-                    CommonMarkerUpdates::None
-                } else {
-                    CommonMarkerUpdates::Advance
+                Some(SpecialMove::Halting { .. }) => {
+                    // Was running and pushed hard in the opposite direction
+                    if let Some(water_volume_entity) = in_water_volume {
+                        commands.entity(entity).insert((
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
+                        ));
+                        CommonMarkerUpdates::None
+                    } else if grounding.is_none() {
+                        commands.entity(entity).remove::<Attachment>();
+                        CommonMarkerUpdates::None
+                    } else if !slope_is_walkable {
+                        commands.entity(entity).insert(SpecialMove::Sliding);
+                        CommonMarkerUpdates::None
+                    } else {
+                        CommonMarkerUpdates::Advance
+                    }
                 }
-            }
-            Some(SpecialMove::Rolling { .. }) => {
-                // Landed fast, or pressed secondary button while running
-                if let Some(water_volume_entity) = in_water_volume {
-                    commands.entity(entity).insert((
-                        Attachment::Submerged {
-                            water_volume_entity,
-                        },
-                        SpecialMove::Halting { progress: 0.0 },
-                    ));
-                    CommonMarkerUpdates::None
-                } else if grounding.is_none() {
-                    commands.entity(entity).remove::<Attachment>();
-                    CommonMarkerUpdates::None
-                } else if horizontal_speed < params.unroll_speed_threshold {
-                    commands.entity(entity).remove::<SpecialMove>();
-                    CommonMarkerUpdates::None
-                } else {
-                    CommonMarkerUpdates::Advance
-                }
-            }
-            Some(SpecialMove::Running) => {
-                // Running on ground
-                if let Some(water_volume_entity) = in_water_volume {
-                    commands.entity(entity).insert((
-                        Attachment::Submerged {
-                            water_volume_entity,
-                        },
-                        SpecialMove::Halting { progress: 0.0 },
-                    ));
-                    CommonMarkerUpdates::None
-                } else if grounding.is_none() {
-                    commands.entity(entity).remove::<Attachment>();
-                    commands.entity(entity).remove::<SpecialMove>();
-                    CommonMarkerUpdates::None
-                } else if !slope_is_walkable {
-                    commands.entity(entity).insert(SpecialMove::Sliding);
-                    CommonMarkerUpdates::None
-                } else if !above_running_speed {
-                    commands.entity(entity).remove::<SpecialMove>();
-                    CommonMarkerUpdates::None
-                } else if input_opposes_motion {
-                    commands
-                        .entity(entity)
-                        .insert(SpecialMove::Halting { progress: 0.0 });
-                    CommonMarkerUpdates::None
-                } else {
-                    CommonMarkerUpdates::Advance
+                Some(SpecialMove::Sliding) => {
+                    // Was on a steep slope (keep sliding even if not anymore)
+                    if let Some(water_volume_entity) = in_water_volume {
+                        commands.entity(entity).insert((
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
+                        ));
+                        CommonMarkerUpdates::None
+                    } else if grounding.is_none() {
+                        commands.entity(entity).remove::<Attachment>();
+                        commands.entity(entity).remove::<SpecialMove>();
+                        CommonMarkerUpdates::None
+                    } else if horizontal_speed < params.unslide_speed_threshold && slope_is_walkable
+                    {
+                        commands.entity(entity).remove::<SpecialMove>();
+                        CommonMarkerUpdates::None
+                    } else {
+                        CommonMarkerUpdates::Advance
+                    }
                 }
-            }
-            Some(SpecialMove::Halting { .. }) => {
-                // Was running and pushed hard in the opposite direction
-                if let Some(water_volume_entity) = in_water_volume {
-                    commands.entity(entity).insert((
-                        Attachment::Submerged {
-                            water_volume_entity,
-                        },
-                        SpecialMove::Halting { progress: 0.0 },
-                    ));
-                    CommonMarkerUpdates::None
-                } else if grounding.is_none() {
-                    commands.entity(entity).remove::<Attachment>();
-                    CommonMarkerUpdates::None
-                } else if !slope_is_walkable {
-                    commands.entity(entity).insert(SpecialMove::Sliding);
-                    CommonMarkerUpdates::None
-                } else {
-                    CommonMarkerUpdates::Advance
+                Some(SpecialMove::Jumping) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Diving) => CommonMarkerUpdates::Advance,  // Should not happen
+                Some(SpecialMove::Climbing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Gliding) => CommonMarkerUpdates::Advance, // Should not happen
+            },
+            Some(Attachment::LedgeGrabbed { .. }) => match special_move {
+                None => {
+                    // Clinging to a ledge, can shimmy along it
+                    if let Some(water_volume_entity) = in_water_volume {
+                        commands.entity(entity).insert((
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
+                        ));
+                        CommonMarkerUpdates::None
+                    } else if !is_holding_ledge {
+                        // Shimmied off the end of the ledge
+                        commands.entity(entity).remove::<Attachment>();
+                        CommonMarkerUpdates::None
+                    } else {
+                        CommonMarkerUpdates::Advance
+                    }
                 }
-            }
-            Some(SpecialMove::Sliding) => {
-                // Was on a steep slope (keep sliding even if not anymore)
-                if let Some(water_volume_entity) = in_water_volume {
-                    commands.entity(entity).insert((
-                        Attachment::Submerged {
-                            water_volume_entity,
-                        },
-                        SpecialMove::Halting { progress: 0.0 },
-                    ));
-                    CommonMarkerUpdates::None
-                } else if grounding.is_none() {
-                    commands.entity(entity).remove::<Attachment>();
-                    commands.entity(entity).remove::<SpecialMove>();
-                    CommonMarkerUpdates::None
-                } else if horizontal_speed < params.unslide_speed_threshold && slope_is_walkable {
-                    commands.entity(entity).remove::<SpecialMove>();
-                    CommonMarkerUpdates::None
-                } else {
-                    CommonMarkerUpdates::Advance
+                Some(SpecialMove::Landing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Rolling { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Running) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Halting { .. }) => {
+                    // Just became attached to the ledge; no control briefly
+                    if let Some(water_volume_entity) = in_water_volume {
+                        commands.entity(entity).insert((
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
+                        ));
+                        CommonMarkerUpdates::None
+                    } else {
+                        CommonMarkerUpdates::Advance
+                    }
                 }
-            }
-            Some(SpecialMove::Jumping) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Diving) => CommonMarkerUpdates::Advance,  // Should not happen
-            Some(SpecialMove::Climbing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-        },
-        Some(Attachment::LedgeGrabbed { .. }) => match special_move {
-            None => {
-                // Holding onto a ledge
-                if let Some(water_volume_entity) = in_water_volume {
-                    commands.entity(entity).insert((
-                        Attachment::Submerged {
-                            water_volume_entity,
-                        },
-                        SpecialMove::Halting { progress: 0.0 },
-                    ));
-                    CommonMarkerUpdates::None
-                } else {
-                    CommonMarkerUpdates::Advance
+                Some(SpecialMove::Sliding) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Jumping) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Diving) => CommonMarkerUpdates::Advance,  // Should not happen
+                Some(SpecialMove::Climbing { .. }) => CommonMarkerUpdates::Advance,
+                Some(SpecialMove::Gliding) => CommonMarkerUpdates::Advance, // Should not happen
+            },
+            Some(Attachment::Walled { .. }) => match special_move {
+                None => {
+                    // Stuck to a wall for a length of time before starting to slip
+                    if let Some(water_volume_entity) = in_water_volume {
+                        commands.entity(entity).insert((
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
+                        ));
+                        CommonMarkerUpdates::None
+                    } else if !is_on_wall {
+                        commands.entity(entity).remove::<Attachment>();
+                        CommonMarkerUpdates::None
+                    } else {
+                        CommonMarkerUpdates::Advance
+                    }
                 }
-            }
-            Some(SpecialMove::Landing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Rolling { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Running) => CommonMarkerUpdates::Advance,        // Should not happen
-            Some(SpecialMove::Halting { .. }) => {
-                // Just became attached to the ledge; no control briefly
-                if let Some(water_volume_entity) = in_water_volume {
-                    commands.entity(entity).insert((
-                        Attachment::Submerged {
-                            water_volume_entity,
-                        },
-                        SpecialMove::Halting { progress: 0.0 },
-                    ));
-                    CommonMarkerUpdates::None
-                } else {
-                    CommonMarkerUpdates::Advance
+                Some(SpecialMove::Landing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Rolling { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Running) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Halting { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Sliding) => {
+                    // Sliding down wall
+                    if let Some(water_volume_entity) = in_water_volume {
+                        commands.entity(entity).insert((
+                            Attachment::Submerged {
+                                water_volume_entity,
+                            },
+                            SpecialMove::Halting { progress: 0.0 },
+                        ));
+                        CommonMarkerUpdates::None
+                    } else if let Some((normal, supporting_entity)) = grounding {
+                        if slope_is_walkable {
+                            commands.entity(entity).insert(Attachment::Grounded {
+                                normal,
+                                supporting_entity,
+                            });
+                        } else {
+                            commands.entity(entity).insert((
+                                Attachment::Grounded {
+                                    normal,
+                                    supporting_entity,
+                                },
+                                SpecialMove::Sliding,
+                            ));
+                        }
+                        CommonMarkerUpdates::None
+                    } else if !is_on_wall {
+                        commands.entity(entity).remove::<Attachment>();
+                        CommonMarkerUpdates::None
+                    } else {
+                        CommonMarkerUpdates::Advance
+                    }
                 }
-            }
-            Some(SpecialMove::Sliding) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Jumping) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Diving) => CommonMarkerUpdates::Advance,  // Should not happen
-            Some(SpecialMove::Climbing { .. }) => CommonMarkerUpdates::Advance,
-        },
-        Some(Attachment::Walled { .. }) => match special_move {
-            None => {
-                // Stuck to a wall for a length of time before starting to slip
-                if let Some(water_volume_entity) = in_water_volume {
-                    commands.entity(entity).insert((
-                        Attachment::Submerged {
-                            water_volume_entity,
-                        },
-                        SpecialMove::Halting { progress: 0.0 },
-                    ));
-                    CommonMarkerUpdates::None
-                } else if !is_on_wall {
-                    commands.entity(entity).remove::<Attachment>();
-                    CommonMarkerUpdates::None
-                } else {
-                    CommonMarkerUpdates::Advance
+                Some(SpecialMove::Jumping) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Diving) => CommonMarkerUpdates::Advance,  // Should not happen
+                Some(SpecialMove::Climbing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Gliding) => CommonMarkerUpdates::Advance, // Should not happen
+            },
+            Some(Attachment::Submerged { .. }) => match special_move {
+                None => {
+                    // Swimming
+                    if in_water_volume.is_none() {
+                        if let Some(water_entity) = on_water_surface_entity {
+                            commands.entity(entity).insert(Attachment::Floating {
+                                water_volume_entity: water_entity,
+                            });
+                            commands.entity(entity).remove::<SpecialMove>();
+                            CommonMarkerUpdates::None
+                        } else {
+                            commands.entity(entity).remove::<Attachment>();
+                            CommonMarkerUpdates::None
+                        }
+                    } else {
+                        CommonMarkerUpdates::Advance
+                    }
                 }
-            }
-            Some(SpecialMove::Landing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Rolling { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Running) => CommonMarkerUpdates::Advance,        // Should not happen
-            Some(SpecialMove::Halting { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Sliding) => {
-                // Sliding down wall
-                if let Some(water_volume_entity) = in_water_volume {
-                    commands.entity(entity).insert((
-                        Attachment::Submerged {
-                            water_volume_entity,
-                        },
-                        SpecialMove::Halting { progress: 0.0 },
-                    ));
-                    CommonMarkerUpdates::None
-                } else if let Some(normal) = grounding {
-                    if slope_is_walkable {
-                        commands
-                            .entity(entity)
-                            .insert(Attachment::Grounded { normal });
+                Some(SpecialMove::Landing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Rolling { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Running) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Halting { .. }) => {
+                    // Just became submerged, no control briefly
+                    if in_water_volume.is_none() {
+                        commands.entity(entity).remove::<Attachment>();
+                        CommonMarkerUpdates::None
                     } else {
-                        commands
-                            .entity(entity)
-                            .insert((Attachment::Grounded { normal }, SpecialMove::Sliding));
+                        CommonMarkerUpdates::Advance
                     }
-                    CommonMarkerUpdates::None
-                } else if !is_on_wall {
-                    commands.entity(entity).remove::<Attachment>();
-                    CommonMarkerUpdates::None
-                } else {
-                    CommonMarkerUpdates::Advance
                 }
-            }
-            Some(SpecialMove::Jumping) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Diving) => CommonMarkerUpdates::Advance,  // Should not happen
-            Some(SpecialMove::Climbing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-        },
-        Some(Attachment::Submerged { .. }) => match special_move {
-            None => {
-                // Swimming
-                if in_water_volume.is_none() {
-                    if let Some(water_entity) = on_water_surface_entity {
-                        commands.entity(entity).insert(Attachment::Floating {
-                            water_volume_entity: water_entity,
-                        });
+                Some(SpecialMove::Sliding) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Jumping) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Diving) => {
+                    // Streaming through water
+                    if in_water_volume.is_none() {
+                        commands.entity(entity).remove::<Attachment>();
+                        CommonMarkerUpdates::None
+                    } else if vertical_speed == 0.0 && horizontal_speed == 0.0 {
                         commands.entity(entity).remove::<SpecialMove>();
                         CommonMarkerUpdates::None
                     } else {
+                        CommonMarkerUpdates::Advance
+                    }
+                }
+                Some(SpecialMove::Climbing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Gliding) => CommonMarkerUpdates::Advance, // Should not happen
+            },
+            Some(Attachment::Floating { .. }) => match special_move {
+                None => {
+                    // Paddling around on the surface
+                    if on_water_surface_entity.is_none() {
                         commands.entity(entity).remove::<Attachment>();
                         CommonMarkerUpdates::None
+                    } else {
+                        CommonMarkerUpdates::Advance
                     }
-                } else {
-                    CommonMarkerUpdates::Advance
                 }
+                Some(SpecialMove::Landing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Rolling { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Running) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Halting { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Sliding) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Jumping) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Diving) => CommonMarkerUpdates::Advance,  // Should not happen
+                Some(SpecialMove::Climbing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
+                Some(SpecialMove::Gliding) => CommonMarkerUpdates::Advance, // Should not happen
+            },
+        };
+
+        // If nothing changed, handle delays on the special move
+        let advance_rate = match next_step {
+            CommonMarkerUpdates::Advance => 1.0,
+            CommonMarkerUpdates::AdvanceRate(rate) => rate,
+            CommonMarkerUpdates::None => {
+                continue;
             }
-            Some(SpecialMove::Landing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Rolling { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Running) => CommonMarkerUpdates::Advance,        // Should not happen
-            Some(SpecialMove::Halting { .. }) => {
-                // Just became submerged, no control briefly
-                if in_water_volume.is_none() {
-                    commands.entity(entity).remove::<Attachment>();
-                    CommonMarkerUpdates::None
+        };
+        match attachment {
+            Some(Attachment::Walled { normal, progress }) => {
+                if special_move.is_none() {
+                    let progress = progress + advance_rate * time.delta_secs();
+                    if progress > params.wall_stick_duration {
+                        commands.entity(entity).insert(SpecialMove::Sliding);
+                    } else {
+                        commands.entity(entity).insert(Attachment::Walled {
+                            normal: *normal,
+                            progress,
+                        });
+                    }
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        match special_move {
+            None => {}
+            Some(SpecialMove::Landing { progress }) => {
+                let progress = progress + advance_rate * time.delta_secs();
+                if progress > params.landing_move_duration {
+                    commands.entity(entity).remove::<SpecialMove>();
                 } else {
-                    CommonMarkerUpdates::Advance
+                    commands
+                        .entity(entity)
+                        .insert(SpecialMove::Landing { progress });
                 }
             }
-            Some(SpecialMove::Sliding) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Jumping) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Diving) => {
-                // Streaming through water
-                if in_water_volume.is_none() {
-                    commands.entity(entity).remove::<Attachment>();
-                    CommonMarkerUpdates::None
-                } else if vertical_speed == 0.0 && horizontal_speed == 0.0 {
+            Some(SpecialMove::Rolling { progress }) => {
+                let progress = progress + advance_rate * time.delta_secs();
+                if progress > params.rolling_move_duration {
                     commands.entity(entity).remove::<SpecialMove>();
-                    CommonMarkerUpdates::None
                 } else {
-                    CommonMarkerUpdates::Advance
+                    commands
+                        .entity(entity)
+                        .insert(SpecialMove::Rolling { progress });
                 }
             }
-            Some(SpecialMove::Climbing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-        },
-        Some(Attachment::Floating { .. }) => match special_move {
-            None => {
-                // Paddling around on the surface
-                if on_water_surface_entity.is_none() {
-                    commands.entity(entity).remove::<Attachment>();
-                    CommonMarkerUpdates::None
+            Some(SpecialMove::Running) => {}
+            Some(SpecialMove::Halting { progress }) => {
+                let duration = match attachment {
+                    Some(Attachment::Submerged { .. }) => params.submersion_move_duration,
+                    Some(Attachment::LedgeGrabbed { .. }) => params.ledge_grab_move_duration,
+                    _ => params.halting_move_duration,
+                };
+                let progress = progress + advance_rate * time.delta_secs();
+                if progress > duration {
+                    commands.entity(entity).remove::<SpecialMove>();
                 } else {
-                    CommonMarkerUpdates::Advance
+                    commands
+                        .entity(entity)
+                        .insert(SpecialMove::Halting { progress });
                 }
             }
-            Some(SpecialMove::Landing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Rolling { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Running) => CommonMarkerUpdates::Advance,        // Should not happen
-            Some(SpecialMove::Halting { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-            Some(SpecialMove::Sliding) => CommonMarkerUpdates::Advance,        // Should not happen
-            Some(SpecialMove::Jumping) => CommonMarkerUpdates::Advance,        // Should not happen
-            Some(SpecialMove::Diving) => CommonMarkerUpdates::Advance,         // Should not happen
-            Some(SpecialMove::Climbing { .. }) => CommonMarkerUpdates::Advance, // Should not happen
-        },
-    };
-
-    // If nothing changed, handle delays on the special move
-    let advance_rate = match next_step {
-        CommonMarkerUpdates::Advance => 1.0,
-        CommonMarkerUpdates::AdvanceRate(rate) => rate,
-        CommonMarkerUpdates::None => {
-            return Ok(());
-        }
-    };
-    match attachment {
-        Some(Attachment::Walled { normal, progress }) => {
-            if special_move.is_none() {
+            Some(SpecialMove::Sliding) => {}
+            Some(SpecialMove::Jumping) => {}
+            Some(SpecialMove::Diving) => {}
+            Some(SpecialMove::Gliding) => {}
+            Some(SpecialMove::Climbing { progress, normal }) => {
                 let progress = progress + advance_rate * time.delta_secs();
-                if progress > params.wall_stick_duration {
-                    commands.entity(entity).insert(SpecialMove::Sliding);
+                if progress > params.climbing_move_duration {
+                    commands
+                        .entity(entity)
+                        .remove::<Attachment>()
+                        .remove::<SpecialMove>();
+                    // Avian syncs the Position from the Transform before the physics step,
+                    // so both have to move onto the ledge
+                    let onto_ledge = params.ledge_grab_required_inset * *normal;
+                    position.0 -= onto_ledge;
+                    transform.translation -= onto_ledge;
                 } else {
-                    commands.entity(entity).insert(Attachment::Walled {
-                        normal: *normal,
+                    commands.entity(entity).insert(SpecialMove::Climbing {
                         progress,
+                        normal: *normal,
                     });
                 }
-                return Ok(());
             }
         }
-        _ => {}
     }
-    match special_move {
-        None => {}
-        Some(SpecialMove::Landing { progress }) => {
-            let progress = progress + advance_rate * time.delta_secs();
-            if progress > params.landing_move_duration {
-                commands.entity(entity).remove::<SpecialMove>();
-            } else {
-                commands
-                    .entity(entity)
-                    .insert(SpecialMove::Landing { progress });
-            }
-        }
-        Some(SpecialMove::Rolling { progress }) => {
-            let progress = progress + advance_rate * time.delta_secs();
-            if progress > params.rolling_move_duration {
-                commands.entity(entity).remove::<SpecialMove>();
-            } else {
-                commands
-                    .entity(entity)
-                    .insert(SpecialMove::Rolling { progress });
-            }
-        }
-        Some(SpecialMove::Running) => {}
-        Some(SpecialMove::Halting { progress }) => {
-            let duration = match attachment {
-                Some(Attachment::Submerged { .. }) => params.submersion_move_duration,
-                _ => params.halting_move_duration,
-            };
-            let progress = progress + advance_rate * time.delta_secs();
-            if progress > duration {
-                commands.entity(entity).remove::<SpecialMove>();
-            } else {
-                commands
-                    .entity(entity)
-                    .insert(SpecialMove::Halting { progress });
-            }
-        }
-        Some(SpecialMove::Sliding) => {}
-        Some(SpecialMove::Jumping) => {}
-        Some(SpecialMove::Diving) => {}
-        Some(SpecialMove::Climbing { progress, normal }) => {
-            let progress = progress + advance_rate * time.delta_secs();
-            if progress > params.climbing_move_duration {
-                commands
-                    .entity(entity)
-                    .remove::<Attachment>()
-                    .remove::<SpecialMove>();
-                transform.translation -= params.ledge_grab_required_inset * normal;
-            } else {
-                commands.entity(entity).insert(SpecialMove::Climbing {
-                    progress,
-                    normal: *normal,
-                });
-            }
+    Ok(())
+}
+
+/// Pick up any [`PowerUp`] a character touches, replacing whatever power-up was held before.
+/// Each power-up goes to the first character found touching it.
+fn collect_power_ups(
+    mut commands: Commands,
+    character_query: Query<(Entity, &Transform), With<CharacterController>>,
+    power_up_query: Query<(Entity, &GlobalTransform, &PowerUp), Without<CharacterController>>,
+) -> Result<(), BevyError> {
+    for (power_up_entity, power_up_transform, power_up) in power_up_query.iter() {
+        let collector = character_query.iter().find(|(_, character_transform)| {
+            let distance = power_up_transform
+                .translation()
+                .distance(character_transform.translation);
+            distance < PLAYER_RADIUS + POWER_UP_PICKUP_PROXIMITY
+        });
+        if let Some((character_entity, _)) = collector {
+            println!(
+                "Character {} picked up {:?} power-up",
+                character_entity, power_up
+            );
+            commands.entity(character_entity).insert(*power_up);
+            commands.entity(power_up_entity).despawn();
         }
     }
-
     Ok(())
 }
 
 fn apply_gravity(
     mut query: Query<(
-        &mut PlayerController,
+        &mut CharacterController,
+        &CharacterHits,
         Option<&Attachment>,
         Option<&SpecialMove>,
     )>,
+    modifier_query: Query<&MovementModifierVolume>,
     params: Res<CharacterControllerParams>,
     time: Res<Time>,
 ) -> Result<(), BevyError> {
-    let Ok((mut controller, attachment, special_move)) = query.single_mut() else {
-        println!("Not running apply_gravity this timestep");
-        return Ok(());
-    };
     let delta_time = time.delta_secs();
-    let (gravity, terminal_velocity) = match attachment {
-        None => match special_move {
-            Some(SpecialMove::Jumping) => (
-                params.jumping_gravity_factor * params.gravity,
-                params.terminal_velocity,
-            ),
-            Some(SpecialMove::Rolling { .. }) => (
-                params.aerial_roll_gravity_factor * params.gravity,
-                params.terminal_velocity,
-            ),
-            _ => (params.gravity, params.terminal_velocity),
-        },
-        Some(Attachment::Grounded { normal }) => {
-            let is_walkable = normal.angle_between(Vector::Y) <= params.max_walking_slope_angle;
-            match is_walkable {
-                true => (Vector::ZERO, params.terminal_velocity),
-                false => (params.gravity, params.terminal_velocity),
+    for (mut controller, hits, attachment, special_move) in query.iter_mut() {
+        let (gravity, terminal_velocity) = match attachment {
+            None => match special_move {
+                Some(SpecialMove::Jumping) => (
+                    params.jumping_gravity_factor * params.gravity,
+                    params.terminal_velocity,
+                ),
+                Some(SpecialMove::Rolling { .. }) => (
+                    params.aerial_roll_gravity_factor * params.gravity,
+                    params.terminal_velocity,
+                ),
+                Some(SpecialMove::Gliding) => (
+                    params.gliding.gravity_factor * params.gravity,
+                    params.gliding.terminal_velocity,
+                ),
+                _ => (params.gravity, params.terminal_velocity),
+            },
+            Some(Attachment::Grounded { normal, .. }) => {
+                let is_walkable = normal.angle_between(Vector::Y) <= params.max_walking_slope_angle;
+                match is_walkable {
+                    true => (Vector::ZERO, params.terminal_velocity),
+                    false => (params.gravity, params.terminal_velocity),
+                }
             }
-        }
-        Some(Attachment::LedgeGrabbed { .. }) => (Vector::ZERO, 0.0),
-        Some(Attachment::Walled { .. }) => match special_move {
-            Some(SpecialMove::Sliding) => (params.gravity, params.wall_slide_terminal_velocity),
-            _ => (Vector::ZERO, 0.0),
-        },
-        Some(Attachment::Submerged { .. }) => match special_move {
-            Some(SpecialMove::Diving) => (Vector::ZERO, params.terminal_velocity),
-            _ => (params.buoyant_gravity, params.buoyant_terminal_velocity),
-        },
-        Some(Attachment::Floating { .. }) => (params.gravity, params.terminal_velocity),
-    };
+            Some(Attachment::LedgeGrabbed { .. }) => (Vector::ZERO, 0.0),
+            Some(Attachment::Walled { .. }) => match special_move {
+                Some(SpecialMove::Sliding) => (params.gravity, params.wall_slide_terminal_velocity),
+                _ => (Vector::ZERO, 0.0),
+            },
+            Some(Attachment::Submerged { .. }) => match special_move {
+                Some(SpecialMove::Diving) => (Vector::ZERO, params.terminal_velocity),
+                _ => (params.buoyant_gravity, params.buoyant_terminal_velocity),
+            },
+            Some(Attachment::Floating { .. }) => (params.gravity, params.terminal_velocity),
+        };
 
-    controller.velocity.y = math::approach_velocity(
-        controller.velocity.y,
-        gravity.y,
-        delta_time,
-        terminal_velocity,
-    );
+        // Modifier volumes only change gravity in states where it applies at all
+        let movement_modifier = hits
+            .movement_modifier_volume
+            .and_then(|volume_entity| modifier_query.get(volume_entity).ok());
+        let gravity = match movement_modifier {
+            Some(modifier) if gravity != Vector::ZERO => {
+                gravity.with_y(modifier.gravity.apply(gravity.y))
+            }
+            _ => gravity,
+        };
 
+        controller.velocity.y = math::approach_velocity(
+            controller.velocity.y,
+            gravity.y,
+            delta_time,
+            terminal_velocity,
+        );
+    }
     Ok(())
 }
 
@@ -918,490 +1218,835 @@ fn apply_inputs(
     mut commands: Commands,
     mut query: Query<(
         Entity,
-        &mut PlayerController,
+        &mut CharacterController,
+        &CharacterHits,
+        &Transform,
+        &mut JumpAssist,
         Option<&Attachment>,
         Option<&SpecialMove>,
+        &MovementIntent,
+        Has<PlayerController>,
     )>,
+    breakables: Query<&Breakable>,
+    parents: Query<&ChildOf>,
+    modifier_query: Query<&MovementModifierVolume>,
+    colliders: Query<&ColliderOf>,
+    bodies: Query<(&Position, &LinearVelocity, &AngularVelocity), Without<CharacterController>>,
+    mut broken_messages: MessageWriter<BreakableBroken>,
+    mut action_prompts: ResMut<ActionPrompts>,
     params: Res<CharacterControllerParams>,
-    inputs: Res<MovementState>,
     time: Res<Time>,
 ) -> Result<(), BevyError> {
-    let Ok((entity, mut controller, attachment, special_move)) = query.single_mut() else {
-        println!("Not running apply_inputs this timestep");
-        return Ok(());
-    };
     let delta_time = time.delta_secs();
-    let (jump_mode, secondary_button_mode, manoevre_mode) = match attachment {
-        Some(Attachment::Grounded { normal }) => match special_move {
-            Some(SpecialMove::Landing { .. }) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::Freeze,
-            ),
-            Some(SpecialMove::Rolling { .. }) => (
-                JumpMode::Shallow {
-                    factor: params.rolling_movement.jump_factor,
-                },
-                SecondaryButtonMode::None,
-                ManoeuvreMode::HorizontalInput(&params.rolling_movement),
-            ),
-            Some(SpecialMove::Running) => (
-                JumpMode::Regular {
-                    factor: params.ground_movement.jump_factor,
-                },
-                SecondaryButtonMode::EnterRoll,
-                ManoeuvreMode::HorizontalInput(&params.ground_movement),
-            ),
-            Some(SpecialMove::Halting { .. }) => (
-                JumpMode::Dive {
-                    upward_factor: params.dive_jump_upward_factor,
-                    horizontal_factor: params.dive_jump_horizontal_factor,
-                },
-                SecondaryButtonMode::None,
-                ManoeuvreMode::HorizontalInput(&params.halting_movement),
-            ),
-            Some(SpecialMove::Sliding) => (
-                JumpMode::Shallow {
-                    factor: params.sliding_movement.jump_factor,
-                },
-                SecondaryButtonMode::None,
-                ManoeuvreMode::HorizontalInput(&params.sliding_movement),
-            ),
-            Some(SpecialMove::Jumping) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::Freewheel,
-            ),
-            Some(SpecialMove::Diving) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::Freewheel,
-            ),
-            Some(SpecialMove::Climbing { .. }) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::Freewheel,
-            ),
-            None => {
-                let normal_angle = normal.angle_between(Vector::Y);
-                let input_mode = match normal_angle <= params.max_walking_slope_angle {
-                    true => ManoeuvreMode::PlanarInput {
-                        manoeuvrability: &params.ground_movement,
-                        normal: *normal,
+    for (
+        entity,
+        mut controller,
+        hits,
+        transform,
+        mut jump_assist,
+        attachment,
+        special_move,
+        inputs,
+        is_player,
+    ) in query.iter_mut()
+    {
+        let (jump_mode, secondary_button_mode, manoevre_mode) = match attachment {
+            Some(Attachment::Grounded { normal, .. }) => match special_move {
+                Some(SpecialMove::Landing { .. }) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freeze,
+                ),
+                Some(SpecialMove::Rolling { .. }) => (
+                    JumpMode::Shallow {
+                        factor: params.rolling_movement.jump_factor,
                     },
-                    false => ManoeuvreMode::HorizontalInput(&params.ground_movement),
-                };
-                (
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::HorizontalInput(&params.rolling_movement),
+                ),
+                Some(SpecialMove::Running) => (
                     JumpMode::Regular {
                         factor: params.ground_movement.jump_factor,
                     },
+                    SecondaryButtonMode::EnterRoll,
+                    ManoeuvreMode::HorizontalInput(&params.ground_movement),
+                ),
+                Some(SpecialMove::Halting { .. }) => (
+                    JumpMode::Dive {
+                        upward_factor: params.dive_jump_upward_factor,
+                        horizontal_factor: params.dive_jump_horizontal_factor,
+                    },
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::HorizontalInput(&params.halting_movement),
+                ),
+                Some(SpecialMove::Sliding) => (
+                    JumpMode::Shallow {
+                        factor: params.sliding_movement.jump_factor,
+                    },
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::HorizontalInput(&params.sliding_movement),
+                ),
+                Some(SpecialMove::Jumping) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freewheel,
+                ),
+                Some(SpecialMove::Diving) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freewheel,
+                ),
+                Some(SpecialMove::Climbing { .. }) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freewheel,
+                ),
+                Some(SpecialMove::Gliding) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freewheel,
+                ),
+                None => {
+                    let normal_angle = normal.angle_between(Vector::Y);
+                    let input_mode = match normal_angle <= params.max_walking_slope_angle {
+                        true => ManoeuvreMode::PlanarInput {
+                            manoeuvrability: &params.ground_movement,
+                            normal: *normal,
+                        },
+                        false => ManoeuvreMode::HorizontalInput(&params.ground_movement),
+                    };
+                    (
+                        JumpMode::Regular {
+                            factor: params.ground_movement.jump_factor,
+                        },
+                        SecondaryButtonMode::None,
+                        input_mode,
+                    )
+                }
+            },
+            Some(Attachment::LedgeGrabbed { normal }) => match special_move {
+                Some(SpecialMove::Halting { .. }) => (
+                    JumpMode::Climb { normal: *normal },
+                    SecondaryButtonMode::KickFromWall { normal: *normal },
+                    ManoeuvreMode::Freeze,
+                ),
+                Some(SpecialMove::Climbing { .. }) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freeze,
+                ),
+                None => (
+                    JumpMode::Climb { normal: *normal },
+                    SecondaryButtonMode::KickFromWall { normal: *normal },
+                    ManoeuvreMode::AlongLedge {
+                        manoeuvrability: &params.ledge_cling_movement,
+                        normal: *normal,
+                        ledge_normal: hits.ledge.as_ref().map_or(Vector::Y, |hit| hit.normal),
+                    },
+                ),
+                _ => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freeze,
+                ),
+            },
+            Some(Attachment::Walled { normal, .. }) => match special_move {
+                Some(SpecialMove::Landing { .. }) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freewheel,
+                ),
+                Some(SpecialMove::Rolling { .. }) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freewheel,
+                ),
+                Some(SpecialMove::Running) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freewheel,
+                ),
+                Some(SpecialMove::Halting { .. }) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freewheel,
+                ),
+                Some(SpecialMove::Sliding) => (
+                    JumpMode::AwayFromNormal {
+                        normal: *normal,
+                        upward_impulse_factor: params.shallow_wall_jump_upward_factor,
+                    },
+                    SecondaryButtonMode::KickFromWall { normal: *normal },
+                    ManoeuvreMode::Freewheel,
+                ),
+                Some(SpecialMove::Jumping) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freewheel,
+                ),
+                Some(SpecialMove::Diving) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freewheel,
+                ),
+                Some(SpecialMove::Climbing { .. }) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freewheel,
+                ),
+                Some(SpecialMove::Gliding) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::Freewheel,
+                ),
+                None => {
+                    let breakable_wall = hits
+                        .wall
+                        .as_ref()
+                        .and_then(|hit| find_breakable(hit.entity, &breakables, &parents))
+                        .filter(|(_, breakable)| breakable.strength <= params.burst_through_impulse)
+                        .map(|(breakable_entity, _)| breakable_entity);
+                    let secondary_mode = match breakable_wall {
+                        Some(breakable_entity) => SecondaryButtonMode::BurstThrough {
+                            breakable_entity,
+                            normal: *normal,
+                        },
+                        None => SecondaryButtonMode::KickFromWall { normal: *normal },
+                    };
+                    (
+                        JumpMode::AwayFromNormal {
+                            normal: *normal,
+                            upward_impulse_factor: params.ground_movement.jump_factor,
+                        },
+                        secondary_mode,
+                        ManoeuvreMode::Freeze,
+                    )
+                }
+            },
+            Some(Attachment::Submerged {
+                water_volume_entity,
+            }) => match special_move {
+                Some(SpecialMove::Landing { .. }) => (
+                    JumpMode::None,
                     SecondaryButtonMode::None,
-                    input_mode,
-                )
-            }
-        },
-        Some(Attachment::LedgeGrabbed { normal, .. }) => (
-            JumpMode::Climb { normal: *normal },
-            SecondaryButtonMode::KickFromWall { normal: *normal },
-            ManoeuvreMode::Freeze,
-        ),
-        Some(Attachment::Walled { normal, .. }) => match special_move {
-            Some(SpecialMove::Landing { .. }) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::Freewheel,
-            ),
-            Some(SpecialMove::Rolling { .. }) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::Freewheel,
-            ),
-            Some(SpecialMove::Running) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::Freewheel,
-            ),
-            Some(SpecialMove::Halting { .. }) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::Freewheel,
-            ),
-            Some(SpecialMove::Sliding) => (
-                JumpMode::AwayFromNormal {
-                    normal: *normal,
-                    upward_impulse_factor: params.shallow_wall_jump_upward_factor,
-                },
-                SecondaryButtonMode::KickFromWall { normal: *normal },
-                ManoeuvreMode::Freewheel,
-            ),
-            Some(SpecialMove::Jumping) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::Freewheel,
-            ),
-            Some(SpecialMove::Diving) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::Freewheel,
-            ),
-            Some(SpecialMove::Climbing { .. }) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::Freewheel,
-            ),
-            None => (
-                JumpMode::AwayFromNormal {
-                    normal: *normal,
-                    upward_impulse_factor: params.ground_movement.jump_factor,
+                    ManoeuvreMode::HorizontalInput(&params.submerged_movement),
+                ),
+                Some(SpecialMove::Rolling { .. }) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::HorizontalInput(&params.submerged_movement),
+                ),
+                Some(SpecialMove::Running) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::HorizontalInput(&params.submerged_movement),
+                ),
+                Some(SpecialMove::Halting { .. }) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::HorizontalInput(&params.submerged_movement),
+                ),
+                Some(SpecialMove::Sliding) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::HorizontalInput(&params.submerged_movement),
+                ),
+                Some(SpecialMove::Jumping) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::HorizontalInput(&params.submerged_movement),
+                ),
+                Some(SpecialMove::Diving) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::RadialMovement(&params.submerged_movement),
+                ),
+                Some(SpecialMove::Climbing { .. }) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::HorizontalInput(&params.submerged_movement),
+                ),
+                Some(SpecialMove::Gliding) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::HorizontalInput(&params.submerged_movement),
+                ),
+                None => (
+                    JumpMode::None,
+                    SecondaryButtonMode::StartStreaming {
+                        water_volume_entity: *water_volume_entity,
+                    },
+                    ManoeuvreMode::HorizontalInput(&params.submerged_movement),
+                ),
+            },
+            Some(Attachment::Floating {
+                water_volume_entity,
+            }) => (
+                JumpMode::Shallow {
+                    factor: params.floating_movement.jump_factor,
                 },
-                SecondaryButtonMode::KickFromWall { normal: *normal },
-                ManoeuvreMode::Freeze,
-            ),
-        },
-        Some(Attachment::Submerged {
-            water_volume_entity,
-        }) => match special_move {
-            Some(SpecialMove::Landing { .. }) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::HorizontalInput(&params.submerged_movement),
-            ),
-            Some(SpecialMove::Rolling { .. }) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::HorizontalInput(&params.submerged_movement),
-            ),
-            Some(SpecialMove::Running) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::HorizontalInput(&params.submerged_movement),
-            ),
-            Some(SpecialMove::Halting { .. }) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::HorizontalInput(&params.submerged_movement),
-            ),
-            Some(SpecialMove::Sliding) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::HorizontalInput(&params.submerged_movement),
-            ),
-            Some(SpecialMove::Jumping) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::HorizontalInput(&params.submerged_movement),
-            ),
-            Some(SpecialMove::Diving) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::RadialMovement(&params.submerged_movement),
-            ),
-            Some(SpecialMove::Climbing { .. }) => (
-                JumpMode::None,
-                SecondaryButtonMode::None,
-                ManoeuvreMode::HorizontalInput(&params.submerged_movement),
-            ),
-            None => (
-                JumpMode::None,
                 SecondaryButtonMode::StartStreaming {
                     water_volume_entity: *water_volume_entity,
                 },
-                ManoeuvreMode::HorizontalInput(&params.submerged_movement),
+                ManoeuvreMode::PlanarInput {
+                    manoeuvrability: &params.floating_movement,
+                    normal: Vec3::Y,
+                },
             ),
-        },
-        Some(Attachment::Floating {
-            water_volume_entity,
-        }) => (
-            JumpMode::Shallow {
-                factor: params.floating_movement.jump_factor,
-            },
-            SecondaryButtonMode::StartStreaming {
-                water_volume_entity: *water_volume_entity,
-            },
-            ManoeuvreMode::PlanarInput {
-                manoeuvrability: &params.floating_movement,
-                normal: Vec3::Y,
+            None => match special_move {
+                Some(SpecialMove::Gliding) => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::HorizontalInput(&params.gliding.movement),
+                ),
+                _ => (
+                    JumpMode::None,
+                    SecondaryButtonMode::None,
+                    ManoeuvreMode::HorizontalInput(&params.aerial_movement),
+                ),
             },
-        ),
-        None => (
-            JumpMode::None,
-            SecondaryButtonMode::None,
-            ManoeuvreMode::HorizontalInput(&params.aerial_movement),
-        ),
-    };
-    match manoevre_mode {
-        ManoeuvreMode::Freeze => {
-            controller.velocity.x = 0.0;
+        };
+
+        // Apply any movement modifier volume the player is inside
+        let movement_modifier = hits
+            .movement_modifier_volume
+            .and_then(|volume_entity| modifier_query.get(volume_entity).ok());
+        let modified_manoeuvrability;
+        let manoevre_mode = match (movement_modifier, manoevre_mode.manoeuvrability()) {
+            (Some(modifier), Some(manoeuvrability)) => {
+                modified_manoeuvrability = manoeuvrability.modified_by(modifier);
+                manoevre_mode.with_manoeuvrability(&modified_manoeuvrability)
+            }
+            _ => manoevre_mode,
+        };
+        let jump_impulse = |factor: Scalar| {
+            let factor = match movement_modifier {
+                Some(modifier) => modifier.jump_factor.apply(factor),
+                None => factor,
+            };
+            factor * params.base_movement.jump_factor
+        };
+
+        // Remember the jump available while attached to something, so that it can still be taken
+        // shortly after leaving it; climbing needs the ledge to still be held, so isn't remembered
+        match (&jump_mode, attachment) {
+            (JumpMode::None | JumpMode::Climb { .. }, _) | (_, None) => {
+                if let Some((_, elapsed)) = &mut jump_assist.coyote_jump {
+                    *elapsed += delta_time;
+                }
+            }
+            (mode, Some(_)) => {
+                jump_assist.coyote_jump = Some((*mode, 0.0));
+            }
         }
-        ManoeuvreMode::Freewheel => {}
-        ManoeuvreMode::HorizontalInput(factors) => {
-            controller.velocity.x = match inputs.input_direction_x.abs() < std::f32::EPSILON {
-                true => math::approach_zero(
-                    controller.velocity.x,
-                    delta_time,
-                    factors.max_speed_factor * params.base_movement.max_speed_factor,
-                    factors.stop_factor * params.base_movement.stop_factor,
-                ),
-                false => {
-                    let input_direction = Vec3::new(inputs.input_direction_x, 0.0, 0.0);
-                    let velocity_projection = controller.velocity.dot(input_direction);
-                    let (base_factor, input_factor) = match velocity_projection.abs()
-                        < std::f32::EPSILON
-                        || controller.velocity.dot(input_direction) > 0.0
-                    {
-                        true => (params.base_movement.input_factor, factors.input_factor),
-                        false => (
-                            params.base_movement.reverse_input_factor,
-                            factors.reverse_input_factor,
-                        ),
-                    };
-                    math::approach_velocity(
+        let (jump_mode, is_coyote_jump) = match (jump_mode, &jump_assist.coyote_jump) {
+            (JumpMode::None, Some((coyote_mode, elapsed)))
+                if attachment.is_none() && *elapsed <= params.coyote_duration =>
+            {
+                (*coyote_mode, true)
+            }
+            (jump_mode, _) => (jump_mode, false),
+        };
+
+        // Let the HUD show what the buttons would do right now
+        if is_player {
+            action_prompts.set_if_neq(ActionPrompts {
+                jump: jump_mode.context_action(),
+                secondary: secondary_button_mode.context_action(),
+            });
+        }
+
+        // Remember a jump press for a while in case no jump is possible yet, such as just before landing
+        jump_assist.since_jump_pressed =
+            match (inputs.just_pressed_jump, jump_assist.since_jump_pressed) {
+                (true, _) => Some(0.0),
+                (false, Some(elapsed)) => Some(elapsed + delta_time),
+                (false, None) => None,
+            };
+        let wants_to_jump = match jump_mode {
+            // Climbing up a ledge that was only just grabbed shouldn't happen by surprise
+            JumpMode::Climb { .. } => inputs.just_pressed_jump,
+            _ => jump_assist
+                .since_jump_pressed
+                .is_some_and(|elapsed| elapsed <= params.jump_buffer_duration),
+        };
+
+        // Jumping off something that's moving keeps its momentum
+        let support_velocity = match attachment {
+            Some(Attachment::Grounded {
+                supporting_entity, ..
+            }) => SupportMotion::find(*supporting_entity, &colliders, &bodies)
+                .map(|motion| motion.velocity_at(&transform.translation))
+                .unwrap_or(Vector::ZERO),
+            _ => Vector::ZERO,
+        };
+
+        match manoevre_mode {
+            ManoeuvreMode::Freeze => {
+                controller.velocity.x = 0.0;
+            }
+            ManoeuvreMode::Freewheel => {}
+            ManoeuvreMode::HorizontalInput(factors) => {
+                controller.velocity.x = match inputs.input_direction_x.abs() < std::f32::EPSILON {
+                    true => math::approach_zero(
                         controller.velocity.x,
-                        input_factor * base_factor * inputs.input_direction_x,
                         delta_time,
-                        factors.max_speed_factor
-                            * params.base_movement.max_speed_factor
-                            * inputs.input_direction_x,
-                    )
+                        factors.max_speed_factor * params.base_movement.max_speed_factor,
+                        factors.stop_factor * params.base_movement.stop_factor,
+                    ),
+                    false => {
+                        let input_direction = Vec3::new(inputs.input_direction_x, 0.0, 0.0);
+                        let velocity_projection = controller.velocity.dot(input_direction);
+                        let (base_factor, input_factor) = match velocity_projection.abs()
+                            < std::f32::EPSILON
+                            || controller.velocity.dot(input_direction) > 0.0
+                        {
+                            true => (params.base_movement.input_factor, factors.input_factor),
+                            false => (
+                                params.base_movement.reverse_input_factor,
+                                factors.reverse_input_factor,
+                            ),
+                        };
+                        math::approach_velocity(
+                            controller.velocity.x,
+                            input_factor * base_factor * inputs.input_direction_x,
+                            delta_time,
+                            factors.max_speed_factor
+                                * params.base_movement.max_speed_factor
+                                * inputs.input_direction_x,
+                        )
+                    }
+                };
+            }
+            ManoeuvreMode::PlanarInput {
+                manoeuvrability: factors,
+                normal,
+            } => {
+                match inputs.input_direction_x.abs() < std::f32::EPSILON {
+                    true => {
+                        // No input, approach zero in current direction
+                        let normal_velocity = controller.velocity.dot(normal) * normal;
+                        let planar_velocity = controller.velocity - normal_velocity;
+                        let speed = planar_velocity.length();
+                        let new_speed = math::approach_zero(
+                            speed,
+                            delta_time,
+                            factors.max_speed_factor * params.base_movement.max_speed_factor,
+                            factors.stop_factor * params.base_movement.stop_factor,
+                        );
+                        controller.velocity = new_speed * planar_velocity.normalize_or_zero();
+                    }
+                    false => {
+                        // Separate velocity in acceleration direction from remaining velocity, and
+                        // accelerate just in that direction (tilt scales the acceleration below)
+                        let input_direction =
+                            Vec3::new(inputs.input_direction_x.signum(), 0.0, 0.0);
+                        let velocity_projection = controller.velocity.dot(input_direction);
+                        let (base_factor, input_factor) = match velocity_projection.abs()
+                            < std::f32::EPSILON
+                            || controller.velocity.dot(input_direction) > 0.0
+                        {
+                            true => (params.base_movement.input_factor, factors.input_factor),
+                            false => (
+                                params.base_movement.reverse_input_factor,
+                                factors.reverse_input_factor,
+                            ),
+                        };
+                        let planar_velocity = velocity_projection.abs() * input_direction;
+                        let perpendicular_velocity = controller.velocity - planar_velocity;
+                        let new_speed = math::approach_velocity(
+                            planar_velocity.length(),
+                            input_factor * base_factor * inputs.input_direction_x.abs(),
+                            delta_time,
+                            factors.max_speed_factor
+                                * params.base_movement.max_speed_factor
+                                * inputs.input_direction_x.abs(),
+                        );
+                        controller.velocity = perpendicular_velocity + new_speed * input_direction;
+                    }
                 }
-            };
-        }
-        ManoeuvreMode::PlanarInput {
-            manoeuvrability: factors,
-            normal,
-        } => {
-            match inputs.input_direction_x.abs() < std::f32::EPSILON {
-                true => {
-                    // No input, approach zero in current direction
-                    let normal_velocity = controller.velocity.dot(normal) * normal;
-                    let planar_velocity = controller.velocity - normal_velocity;
-                    let speed = planar_velocity.length();
+            }
+            ManoeuvreMode::RadialMovement(factors) => {
+                let input_vector =
+                    Vec3::new(inputs.input_direction_x, inputs.input_direction_y, 0.0)
+                        .clamp_length_max(1.0);
+                let current_speed = controller.velocity.length();
+                let approach_speed = input_vector.length();
+                if approach_speed == 0.0 {
                     let new_speed = math::approach_zero(
-                        speed,
+                        current_speed,
                         delta_time,
                         factors.max_speed_factor * params.base_movement.max_speed_factor,
                         factors.stop_factor * params.base_movement.stop_factor,
                     );
-                    controller.velocity = new_speed * planar_velocity.normalize_or_zero();
-                }
-                false => {
-                    // Separate velocity in acceleration direction from remaining velocity, and
-                    // accelerate just in that direction
-                    let input_direction = Vec3::new(inputs.input_direction_x, 0.0, 0.0);
-                    let velocity_projection = controller.velocity.dot(input_direction);
-                    let (base_factor, input_factor) = match velocity_projection.abs()
-                        < std::f32::EPSILON
-                        || controller.velocity.dot(input_direction) > 0.0
-                    {
-                        true => (params.base_movement.input_factor, factors.input_factor),
-                        false => (
-                            params.base_movement.reverse_input_factor,
-                            factors.reverse_input_factor,
-                        ),
-                    };
-                    let planar_velocity = velocity_projection.abs() * input_direction;
-                    let perpendicular_velocity = controller.velocity - planar_velocity;
+                    controller.velocity = new_speed * controller.velocity.normalize_or_zero();
+                } else {
                     let new_speed = math::approach_velocity(
-                        planar_velocity.length(),
-                        input_factor * base_factor * inputs.input_direction_x.abs(),
+                        current_speed,
+                        factors.input_factor * params.base_movement.input_factor * approach_speed,
                         delta_time,
                         factors.max_speed_factor
                             * params.base_movement.max_speed_factor
-                            * inputs.input_direction_x.abs(),
+                            * approach_speed,
+                    );
+                    let current_direction = controller.velocity.y.atan2(controller.velocity.x);
+                    let approach_direction = input_vector.y.atan2(input_vector.x);
+                    let relative_approach_direction = math::float_modulus(
+                        approach_direction - current_direction,
+                        2.0 * std::f32::consts::PI,
+                    );
+                    let relative_new_direction = math::approach_velocity(
+                        0.0,
+                        relative_approach_direction.signum()
+                            * params.streaming_angular_acceleration,
+                        delta_time,
+                        relative_approach_direction,
                     );
-                    controller.velocity = perpendicular_velocity + new_speed * input_direction;
+                    let new_direction = current_direction + relative_new_direction;
+                    controller.velocity =
+                        new_speed * Vec3::new(new_direction.cos(), new_direction.sin(), 0.0);
                 }
             }
-        }
-        ManoeuvreMode::RadialMovement(factors) => {
-            let input_vector = Vec3::new(inputs.input_direction_x, inputs.input_direction_y, 0.0)
-                .normalize_or_zero();
-            let current_speed = controller.velocity.length();
-            let approach_speed = input_vector.length();
-            if approach_speed == 0.0 {
-                let new_speed = math::approach_zero(
-                    current_speed,
-                    delta_time,
-                    factors.max_speed_factor * params.base_movement.max_speed_factor,
-                    factors.stop_factor * params.base_movement.stop_factor,
-                );
-                controller.velocity = new_speed * controller.velocity.normalize_or_zero();
-            } else {
-                let new_speed = math::approach_velocity(
-                    current_speed,
-                    factors.input_factor * params.base_movement.input_factor,
-                    delta_time,
-                    factors.max_speed_factor * params.base_movement.max_speed_factor,
-                );
-                let current_direction = controller.velocity.y.atan2(controller.velocity.x);
-                let approach_direction = input_vector.y.atan2(input_vector.x);
-                let relative_approach_direction = math::float_modulus(
-                    approach_direction - current_direction,
-                    2.0 * std::f32::consts::PI,
-                );
-                let relative_new_direction = math::approach_velocity(
-                    0.0,
-                    relative_approach_direction.signum() * params.streaming_angular_acceleration,
-                    delta_time,
-                    relative_approach_direction,
-                );
-                let new_direction = current_direction + relative_new_direction;
-                controller.velocity =
-                    new_speed * Vec3::new(new_direction.cos(), new_direction.sin(), 0.0);
-            }
-        }
-    }
-    if inputs.just_pressed_jump {
-        match jump_mode {
-            JumpMode::None => {}
-            JumpMode::Regular { factor } => {
-                controller.velocity.y += factor * params.base_movement.jump_factor;
-                commands.entity(entity).remove::<Attachment>();
-                commands.entity(entity).insert(SpecialMove::Jumping);
-            }
-            JumpMode::Dive {
-                upward_factor,
-                horizontal_factor,
-            } => {
-                let input_direction = Vec3::new(inputs.input_direction_x, 0.0, 0.0);
-                controller.velocity.y += upward_factor * params.base_movement.jump_factor;
-                controller.velocity += horizontal_factor * input_direction;
-                commands.entity(entity).remove::<Attachment>();
-                commands.entity(entity).insert(SpecialMove::Diving);
-            }
-            JumpMode::Shallow { factor } => {
-                controller.velocity.y += factor * params.base_movement.jump_factor;
-                commands.entity(entity).remove::<Attachment>();
-                commands.entity(entity).insert(SpecialMove::Jumping);
-            }
-            JumpMode::AwayFromNormal {
+            ManoeuvreMode::AlongLedge {
+                manoeuvrability: factors,
                 normal,
-                upward_impulse_factor,
+                ledge_normal,
             } => {
-                controller.velocity.y += upward_impulse_factor * params.base_movement.jump_factor;
-                controller.velocity +=
-                    normal.with_y(0.0).normalize_or_zero() * params.wall_jump_outward_impulse;
-                commands.entity(entity).remove::<Attachment>();
-                commands.entity(entity).insert(SpecialMove::Jumping);
+                // Horizontal input moves along the ledge's edge, where the wall meets the ledge
+                // top, as it runs through the play plane; pushing right heads right along it. An
+                // edge running straight into the screen has no length in the play plane.
+                let edge = normal.cross(ledge_normal).with_z(0.0);
+                let (tangent, input_along_ledge) = match edge.length() > LEDGE_EDGE_EPSILON {
+                    true if edge.x < 0.0 => (-edge.normalize(), inputs.input_direction_x),
+                    true => (edge.normalize(), inputs.input_direction_x),
+                    false => (Vector::ZERO, 0.0),
+                };
+                let speed_along_ledge = controller.velocity.dot(tangent);
+                let new_speed = match input_along_ledge.abs() < std::f32::EPSILON {
+                    true => math::approach_zero(
+                        speed_along_ledge,
+                        delta_time,
+                        factors.max_speed_factor * params.base_movement.max_speed_factor,
+                        factors.stop_factor * params.base_movement.stop_factor,
+                    ),
+                    false => {
+                        let (base_factor, input_factor) =
+                            match speed_along_ledge * input_along_ledge >= 0.0 {
+                                true => (params.base_movement.input_factor, factors.input_factor),
+                                false => (
+                                    params.base_movement.reverse_input_factor,
+                                    factors.reverse_input_factor,
+                                ),
+                            };
+                        math::approach_velocity(
+                            speed_along_ledge,
+                            input_factor * base_factor * input_along_ledge,
+                            delta_time,
+                            factors.max_speed_factor
+                                * params.base_movement.max_speed_factor
+                                * input_along_ledge,
+                        )
+                    }
+                };
+                controller.velocity = new_speed * tangent;
+            }
+        }
+        if wants_to_jump && jump_mode != JumpMode::None {
+            jump_assist.since_jump_pressed = None;
+            jump_assist.coyote_jump = None;
+            if is_coyote_jump {
+                // Falling since leaving the surface shouldn't take away from the jump
+                controller.velocity.y = controller.velocity.y.max(0.0);
             }
-            JumpMode::Climb { normal } => {
-                commands.entity(entity).insert(SpecialMove::Climbing {
-                    progress: 0.0,
+            match jump_mode {
+                JumpMode::None => {}
+                JumpMode::Regular { factor } => {
+                    controller.velocity.y += jump_impulse(factor);
+                    controller.velocity += support_velocity;
+                    commands.entity(entity).remove::<Attachment>();
+                    commands.entity(entity).insert(SpecialMove::Jumping);
+                }
+                JumpMode::Dive {
+                    upward_factor,
+                    horizontal_factor,
+                } => {
+                    let input_direction = Vec3::new(inputs.input_direction_x, 0.0, 0.0);
+                    controller.velocity.y += jump_impulse(upward_factor);
+                    controller.velocity += horizontal_factor * input_direction + support_velocity;
+                    commands.entity(entity).remove::<Attachment>();
+                    commands.entity(entity).insert(SpecialMove::Diving);
+                }
+                JumpMode::Shallow { factor } => {
+                    controller.velocity.y += jump_impulse(factor);
+                    controller.velocity += support_velocity;
+                    commands.entity(entity).remove::<Attachment>();
+                    commands.entity(entity).insert(SpecialMove::Jumping);
+                }
+                JumpMode::AwayFromNormal {
                     normal,
-                });
+                    upward_impulse_factor,
+                } => {
+                    controller.velocity.y += jump_impulse(upward_impulse_factor);
+                    controller.velocity +=
+                        normal.with_y(0.0).normalize_or_zero() * params.wall_jump_outward_impulse;
+                    commands.entity(entity).remove::<Attachment>();
+                    commands.entity(entity).insert(SpecialMove::Jumping);
+                }
+                JumpMode::Climb { normal } => {
+                    commands.entity(entity).insert(SpecialMove::Climbing {
+                        progress: 0.0,
+                        normal,
+                    });
+                }
             }
         }
-    }
-    if inputs.just_pressed_secondary {
-        match secondary_button_mode {
-            SecondaryButtonMode::None => {}
-            SecondaryButtonMode::EnterRoll => {
-                commands
-                    .entity(entity)
-                    .insert(SpecialMove::Rolling { progress: 0.0 });
-            }
-            SecondaryButtonMode::KickFromWall { normal } => {
-                controller.velocity +=
-                    normal.with_y(0.0).normalize_or_zero() * params.wall_jump_outward_impulse;
+        if inputs.just_pressed_secondary {
+            if secondary_button_mode != SecondaryButtonMode::None {
+                // Whatever this does, it's not to be followed by a jump from what was left behind
+                jump_assist.coyote_jump = None;
             }
-            SecondaryButtonMode::StartStreaming {
-                water_volume_entity,
-            } => {
-                controller.velocity += params.streaming_impulse
-                    * Vec3::new(inputs.input_direction_x, inputs.input_direction_y, 0.0);
-                commands
-                    .entity(entity)
-                    .insert(Attachment::Submerged {
-                        water_volume_entity,
-                    })
-                    .insert(SpecialMove::Diving);
+            match secondary_button_mode {
+                SecondaryButtonMode::None => {}
+                SecondaryButtonMode::EnterRoll => {
+                    commands
+                        .entity(entity)
+                        .insert(SpecialMove::Rolling { progress: 0.0 });
+                }
+                SecondaryButtonMode::KickFromWall { normal } => {
+                    controller.velocity +=
+                        normal.with_y(0.0).normalize_or_zero() * params.wall_jump_outward_impulse;
+                    commands.entity(entity).remove::<Attachment>();
+                }
+                SecondaryButtonMode::BurstThrough {
+                    breakable_entity,
+                    normal,
+                } => {
+                    controller.velocity -=
+                        normal.with_y(0.0).normalize_or_zero() * params.burst_through_impulse;
+                    broken_messages.write(BreakableBroken {
+                        breakable_entity,
+                        breaker_entity: entity,
+                        impact_speed: params.burst_through_impulse,
+                    });
+                    controller.burst_breakable = Some(breakable_entity);
+                    commands
+                        .entity(entity)
+                        .remove::<Attachment>()
+                        .insert(SpecialMove::Rolling { progress: 0.0 });
+                }
+                SecondaryButtonMode::StartStreaming {
+                    water_volume_entity,
+                } => {
+                    controller.velocity += params.streaming_impulse
+                        * Vec3::new(inputs.input_direction_x, inputs.input_direction_y, 0.0);
+                    commands
+                        .entity(entity)
+                        .insert(Attachment::Submerged {
+                            water_volume_entity,
+                        })
+                        .insert(SpecialMove::Diving);
+                }
             }
         }
+        controller.velocity.z = 0.0;
     }
-    controller.velocity.z = 0.0;
     Ok(())
 }
 
-/// Static bodies don't get updated by the physics engine, allowing a custom resolver to work.
+/// Collide and slide along the player's velocity, then hand the result to Avian as the
+/// kinematic body's velocity for this physics step. Avian then moves the Position exactly
+/// there, while dynamic bodies in the way get a contact response from the player's motion.
 fn move_player(
     mut commands: Commands,
     mut controllers_query: Query<(
         Entity,
-        &mut Transform,
-        &mut PlayerController,
+        &Position,
+        &mut Rotation,
+        &mut LinearVelocity,
+        &mut CharacterController,
         &mut Facing,
         Option<&Attachment>,
+        Option<&SpecialMove>,
     )>,
+    breakables: Query<&Breakable>,
+    parents: Query<&ChildOf>,
+    colliders: Query<&ColliderOf>,
+    bodies: Query<(&Position, &LinearVelocity, &AngularVelocity), Without<CharacterController>>,
+    collider_bodies: Query<(Entity, &ColliderOf)>,
+    rigid_bodies: Query<&RigidBody>,
+    water_query: Query<Entity, With<WaterVolume>>,
+    collider_query: Query<(&Collider, &Position, &Rotation), Without<CharacterController>>,
+    mut broken_messages: MessageWriter<BreakableBroken>,
+    mut depenetration_stats: ResMut<DepenetrationStats>,
     spatial_queries: Res<SpatialQueryPipeline>,
-    water_and_player_entities: Res<PlayerAndWaterEntities>,
     params: Res<CharacterControllerParams>,
     time: Res<Time>,
 ) -> Result<(), BevyError> {
-    let Ok((entity, mut transform, mut controller, mut facing, attachment)) =
-        controllers_query.single_mut()
-    else {
-        println!("Not running move_player this timestep");
+    let delta_time = time.delta_secs();
+    if delta_time == 0.0 {
         return Ok(());
-    };
+    }
 
-    let shape = PlayerController::collider(params.collider_skin_thickness);
-    let attempted_displacement = time.delta_secs() * controller.velocity;
-    let is_grounded = match attachment {
-        Some(Attachment::Grounded { .. }) => true,
-        _ => false,
-    };
+    // Dynamic bodies get pushed by the contact solver rather than blocking the player
+    let dynamic_colliders: Vec<Entity> = collider_bodies
+        .iter()
+        .filter(|(_, collider_of)| {
+            rigid_bodies
+                .get(collider_of.body)
+                .is_ok_and(|rigid_body| rigid_body.is_dynamic())
+        })
+        .map(|(collider_entity, _)| collider_entity)
+        .collect();
+
+    for (
+        entity,
+        position,
+        mut rotation,
+        mut linear_velocity,
+        mut controller,
+        mut facing,
+        attachment,
+        special_move,
+    ) in controllers_query.iter_mut()
+    {
+        // Ride along with whatever is underfoot, such as a moving or rotating platform; the
+        // controller's velocity stays relative to it
+        let carried_displacement = match attachment {
+            Some(Attachment::Grounded {
+                supporting_entity, ..
+            }) => SupportMotion::find(*supporting_entity, &colliders, &bodies)
+                .map(|motion| motion.displacement_at(&position.0, delta_time))
+                .unwrap_or(Vector::ZERO),
+            _ => Vector::ZERO,
+        };
+
+        let solid_filter = SpatialQueryFilter::default().with_excluded_entities(
+            water_query
+                .iter()
+                .chain([entity])
+                .chain(dynamic_colliders.iter().copied()),
+        );
 
-    let from_position = match attachment {
-        Some(Attachment::Floating { .. }) => {
-            let internal_cast_distance = 0.5 * PLAYER_HEIGHT;
-            transform.translation + internal_cast_distance * Vec3::Y
+        // Recover from starting the step inside geometry, which the sweep would otherwise pass
+        // straight through
+        let mut start_position = position.0 + carried_displacement;
+        if let Some((push, collider_entity)) = find_depenetration(
+            &spatial_queries,
+            &solid_filter,
+            &params,
+            &collider_query,
+            &start_position,
+        ) {
+            start_position += push;
+            depenetration_stats.recoveries += 1;
+            depenetration_stats.total_distance += push.length();
+            depenetration_stats.last_position = start_position;
+            depenetration_stats.last_entity = Some(collider_entity);
         }
-        _ => transform.translation,
-    };
-    let entity_filter = match attachment {
-        Some(Attachment::Floating { .. }) => {
-            SpatialQueryFilter::default().with_excluded_entities([entity])
+
+        let shape = CharacterController::collider(params.collider_skin_thickness);
+        let attempted_displacement = delta_time * controller.velocity;
+        let is_grounded = match attachment {
+            Some(Attachment::Grounded { .. }) => true,
+            _ => false,
+        };
+
+        let from_position = match attachment {
+            Some(Attachment::Floating { .. }) => {
+                let internal_cast_distance = 0.5 * PLAYER_HEIGHT;
+                start_position + internal_cast_distance * Vec3::Y
+            }
+            _ => start_position,
+        };
+        let mut entity_filter = match attachment {
+            Some(Attachment::Floating { .. }) => SpatialQueryFilter::default()
+                .with_excluded_entities(
+                    std::iter::once(entity).chain(dynamic_colliders.iter().copied()),
+                ),
+            _ => solid_filter,
+        };
+
+        // A fast enough roll bursts through a breakable in the way, keeping its momentum
+        let burst_breakable = controller.burst_breakable.take();
+        if let Some(SpecialMove::Rolling { .. }) = special_move {
+            if let Ok(direction) = Dir3::new(attempted_displacement) {
+                let config = ShapeCastConfig {
+                    max_distance: attempted_displacement.length() + params.collider_skin_thickness,
+                    ..default()
+                };
+                if let Some(hit) = spatial_queries.cast_shape(
+                    &shape,
+                    from_position,
+                    Quaternion::default(),
+                    direction,
+                    &config,
+                    &entity_filter,
+                ) {
+                    let impact_speed = -controller.velocity.dot(hit.normal1);
+                    if let Some((breakable_entity, breakable)) =
+                        find_breakable(hit.entity, &breakables, &parents)
+                    {
+                        if burst_breakable == Some(breakable_entity) {
+                            entity_filter.excluded_entities.insert(hit.entity);
+                        } else if impact_speed >= breakable.strength {
+                            broken_messages.write(BreakableBroken {
+                                breakable_entity,
+                                breaker_entity: entity,
+                                impact_speed,
+                            });
+                            entity_filter.excluded_entities.insert(hit.entity);
+                        }
+                    }
+                }
+            }
         }
-        _ => SpatialQueryFilter::default()
-            .with_excluded_entities(water_and_player_entities.excluded_entities()),
-    };
 
-    let result = move_and_collide_and_slide(
-        spatial_queries,
-        params,
-        &shape,
-        &entity_filter,
-        from_position,
-        attempted_displacement,
-        0,
-        controller.velocity.length(),
-        is_grounded,
-    );
-    let travel = result.new_position - from_position;
-    transform.translation += travel;
-
-    facing.angle = update_facing(&facing, &travel);
-    transform.rotation = Quat::from_rotation_y(facing.angle);
-
-    if let Some((attachment, special_move)) = result.new_attachment {
-        commands.entity(entity).insert(attachment);
-        if let Some(special_move) = special_move {
-            commands.entity(entity).insert(special_move);
-        } else {
-            commands.entity(entity).remove::<SpecialMove>();
+        let result = match attachment {
+            Some(Attachment::LedgeGrabbed { normal }) => shimmy_along_ledge(
+                &spatial_queries,
+                &entity_filter,
+                &params,
+                &from_position,
+                &attempted_displacement,
+                normal,
+            ),
+            _ => move_and_collide_and_slide(
+                &spatial_queries,
+                &params,
+                &shape,
+                &entity_filter,
+                from_position,
+                attempted_displacement,
+                0,
+                controller.velocity.length(),
+                is_grounded,
+            ),
+        };
+        let travel = result.new_position - from_position;
+        linear_velocity.0 = (start_position - position.0 + travel) / delta_time;
+
+        facing.angle = update_facing(&facing, &travel);
+        rotation.0 = Quat::from_rotation_y(facing.angle);
+
+        if let Some((attachment, special_move)) = result.new_attachment {
+            commands.entity(entity).insert(attachment);
+            if let Some(special_move) = special_move {
+                commands.entity(entity).insert(special_move);
+            } else {
+                commands.entity(entity).remove::<SpecialMove>();
+            }
         }
-        commands.entity(entity).remove::<SpecialMove>();
+        controller.velocity = match result.new_velocity {
+            Some(velocity) => velocity,
+            None => travel / delta_time,
+        };
     }
-    controller.velocity = match result.new_velocity {
-        Some(velocity) => velocity,
-        None => travel / time.delta_secs(),
-    };
     Ok(())
 }
 
 fn move_and_collide_and_slide(
-    spatial_queries: Res<SpatialQueryPipeline>,
-    params: Res<CharacterControllerParams>,
+    spatial_queries: &SpatialQueryPipeline,
+    params: &CharacterControllerParams,
     shape: &Collider,
     entity_filter: &SpatialQueryFilter,
     position: Vec3,
@@ -1437,9 +2082,6 @@ fn move_and_collide_and_slide(
             new_velocity: None,
         },
         Some(hit) => {
-            if hit.distance == 0.0 {
-                println!("WARNING: Looks like the player is intersecting a static body!")
-            }
             let travel = (hit.distance - params.collider_skin_thickness) * direction;
             let remaining_displacement = attempted_displacement - travel;
             let remaining_along_normal = remaining_displacement.project_onto(hit.normal2);
@@ -1449,9 +2091,9 @@ fn move_and_collide_and_slide(
             // Check wall hits and find new attachments if airborne
             if !is_grounded {
                 if let Some(movement) = check_aerial_hit_movement(
-                    &spatial_queries,
+                    spatial_queries,
                     &entity_filter,
-                    &params,
+                    params,
                     speed,
                     &attempted_displacement,
                     &collision_position,
@@ -1483,3 +2125,187 @@ fn move_and_collide_and_slide(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::CharacterControllerPlugin;
+    use bevy::{state::app::StatesPlugin, time::TimeUpdateStrategy};
+    use std::time::Duration;
+
+    /// How far in front of a wall a hanging player's collider sits
+    const WALL_GAP: f32 = 0.05;
+
+    /// A headless app with the controller and Avian, advancing one fixed step per update
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            bevy::transform::TransformPlugin,
+            bevy::asset::AssetPlugin::default(),
+            bevy::mesh::MeshPlugin,
+            StatesPlugin,
+            PhysicsPlugins::default(),
+            CharacterControllerPlugin,
+        ))
+        .add_message::<BreakableBroken>()
+        .insert_state(AppState::Game);
+        let timestep = app.world().resource::<Time<Fixed>>().timestep();
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(timestep));
+        app
+    }
+
+    /// A static box with its top face at y = 0
+    fn spawn_block(app: &mut App, centre_x: f32, centre_z: f32, size: Vec3) {
+        app.world_mut().spawn((
+            RigidBody::Static,
+            Collider::cuboid(size.x, size.y, size.z),
+            Transform::from_xyz(centre_x, -0.5 * size.y, centre_z),
+        ));
+    }
+
+    /// A player hanging from the top of a wall, with its feet level with the ledge top
+    fn spawn_hanging_player(
+        app: &mut App,
+        translation: Vec3,
+        normal: Vector,
+        special_move: SpecialMove,
+    ) -> Entity {
+        app.world_mut()
+            .spawn((
+                PlayerController,
+                Transform::from_translation(translation),
+                Attachment::LedgeGrabbed { normal },
+                special_move,
+            ))
+            .id()
+    }
+
+    fn run_steps(app: &mut App, steps: usize) {
+        for _ in 0..steps {
+            app.update();
+        }
+    }
+
+    fn position(app: &App, entity: Entity) -> Vector {
+        app.world().get::<Position>(entity).unwrap().0
+    }
+
+    fn is_holding_ledge(app: &App, entity: Entity) -> bool {
+        matches!(
+            app.world().get::<Attachment>(entity),
+            Some(Attachment::LedgeGrabbed { .. })
+        )
+    }
+
+    /// Hold right for a second, once the stall after grabbing the ledge is over
+    fn shimmy_right(app: &mut App, player: Entity) {
+        run_steps(app, 48);
+        assert!(app.world().get::<SpecialMove>(player).is_none());
+        app.world_mut()
+            .get_mut::<MovementIntent>(player)
+            .unwrap()
+            .input_direction_x = 1.0;
+        run_steps(app, 96);
+    }
+
+    #[test]
+    fn finished_climb_leaves_the_player_on_the_ledge() {
+        let mut app = app();
+        // A wall to the left of the player, facing +X, with its top level with the feet
+        let wall_width = 2.0;
+        spawn_block(
+            &mut app,
+            -(PLAYER_RADIUS + WALL_GAP + 0.5 * wall_width),
+            0.0,
+            Vec3::new(wall_width, 3.0, 4.0),
+        );
+        let climbing_move_duration = app
+            .world()
+            .resource::<CharacterControllerParams>()
+            .climbing_move_duration;
+        let player = spawn_hanging_player(
+            &mut app,
+            Vec3::new(0.0, 0.5 * PLAYER_HEIGHT, 0.0),
+            Vector::X,
+            SpecialMove::Climbing {
+                progress: climbing_move_duration - 0.001,
+                normal: Vector::X,
+            },
+        );
+
+        let mut steps = 0;
+        while app.world().get::<SpecialMove>(player).is_some() {
+            assert!(steps < 20, "climb didn't finish");
+            app.update();
+            steps += 1;
+        }
+        assert!(app.world().get::<Attachment>(player).is_none());
+        run_steps(&mut app, 1);
+        let end = position(&app, player);
+        assert!(end.x < -0.25, "didn't move onto the ledge, at {end}");
+        assert!(
+            end.y > 0.5 * PLAYER_HEIGHT - 0.1,
+            "fell off the ledge, at {end}"
+        );
+    }
+
+    #[test]
+    fn shimmy_moves_along_a_ledge_running_across_the_play_plane() {
+        let mut app = app();
+        // A wall behind the play plane, facing the camera
+        let wall_depth = 1.0;
+        spawn_block(
+            &mut app,
+            0.0,
+            -(PLAYER_RADIUS + WALL_GAP + 0.5 * wall_depth),
+            Vec3::new(20.0, 3.0, wall_depth),
+        );
+        let start = Vec3::new(0.0, 0.5 * PLAYER_HEIGHT, 0.0);
+        let player = spawn_hanging_player(
+            &mut app,
+            start,
+            Vector::Z,
+            SpecialMove::Halting { progress: 0.0 },
+        );
+
+        shimmy_right(&mut app, player);
+        let end = position(&app, player);
+        assert!(is_holding_ledge(&app, player));
+        assert!(end.x > start.x + 0.1, "shimmied from {start} to {end}");
+        assert!(
+            (end.y - start.y).abs() < 0.1,
+            "shimmied from {start} to {end}"
+        );
+        assert!(end.z.abs() < 1e-3, "left the play plane at {end}");
+    }
+
+    #[test]
+    fn shimmy_on_an_x_facing_wall_keeps_the_player_on_the_ledge_and_the_play_plane() {
+        let mut app = app();
+        // A wall to the left of the player, facing +X; its ledge runs into the screen
+        let wall_width = 2.0;
+        spawn_block(
+            &mut app,
+            -(PLAYER_RADIUS + WALL_GAP + 0.5 * wall_width),
+            0.0,
+            Vec3::new(wall_width, 3.0, 4.0),
+        );
+        let start = Vec3::new(0.0, 0.5 * PLAYER_HEIGHT, 0.0);
+        let player = spawn_hanging_player(
+            &mut app,
+            start,
+            Vector::X,
+            SpecialMove::Halting { progress: 0.0 },
+        );
+
+        shimmy_right(&mut app, player);
+        let end = position(&app, player);
+        assert!(is_holding_ledge(&app, player));
+        assert!(
+            (end - start).length() < 0.1,
+            "drifted from {start} to {end}"
+        );
+        assert!(end.z.abs() < 1e-3, "left the play plane at {end}");
+    }
+}
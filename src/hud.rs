@@ -0,0 +1,175 @@
+use crate::{
+    app_draw_layer,
+    controller::{ActionPrompts, ContextAction},
+    input::{InputAction, InputBindings},
+    state::AppState,
+};
+use bevy::{camera::visibility::RenderLayers, prelude::*};
+
+const PROMPT_POP_DURATION: f32 = 0.25;
+const PROMPT_POP_SCALE: f32 = 1.4;
+
+/// One of the button prompts in the corner of the HUD, like the action icons in N64 Zelda.
+/// It shows the key for its button and what pressing it would do, and pops whenever that
+/// changes.
+#[derive(Component)]
+struct ActionPromptWidget {
+    button: InputAction,
+    shown: Option<ContextAction>,
+    since_changed: f32,
+    key_text: Entity,
+    label_text: Entity,
+}
+
+#[derive(Component)]
+struct ActionPromptsRoot;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Game), spawn_action_prompts)
+            .add_systems(OnExit(AppState::Game), remove_action_prompts)
+            .add_systems(
+                Update,
+                update_action_prompts.run_if(in_state(AppState::Game)),
+            );
+    }
+}
+
+fn spawn_action_prompts(mut commands: Commands) {
+    let root = commands
+        .spawn((
+            ActionPromptsRoot,
+            RenderLayers::layer(app_draw_layer::HUD),
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(16.0),
+                right: Val::Px(16.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(8.0),
+                ..default()
+            },
+        ))
+        .id();
+    for button in [InputAction::Jump, InputAction::Secondary] {
+        let key_text = commands
+            .spawn((
+                Text::default(),
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
+                TextColor(Color::BLACK),
+            ))
+            .id();
+        let icon = commands
+            .spawn((
+                Node {
+                    width: Val::Px(48.0),
+                    height: Val::Px(48.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                BorderRadius::MAX,
+                BackgroundColor(Color::srgb(0.8, 0.8, 0.1)),
+            ))
+            .add_child(key_text)
+            .id();
+        let label_text = commands
+            .spawn((
+                Text::default(),
+                TextFont {
+                    font_size: 24.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ))
+            .id();
+        let widget = commands
+            .spawn((
+                ActionPromptWidget {
+                    button,
+                    shown: None,
+                    since_changed: PROMPT_POP_DURATION,
+                    key_text,
+                    label_text,
+                },
+                Node {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(8.0),
+                    ..default()
+                },
+                Visibility::Hidden,
+            ))
+            .add_children(&[icon, label_text])
+            .id();
+        commands.entity(root).add_child(widget);
+    }
+}
+
+fn remove_action_prompts(
+    mut commands: Commands,
+    root_query: Query<Entity, With<ActionPromptsRoot>>,
+) {
+    for root in root_query.iter() {
+        commands.entity(root).despawn();
+    }
+}
+
+/// Short name for a key on an icon, e.g. "L" rather than "KeyL"
+fn key_label(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    match name
+        .strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+    {
+        Some(short_name) => short_name.to_owned(),
+        None => name,
+    }
+}
+
+fn update_action_prompts(
+    prompts: Res<ActionPrompts>,
+    bindings: Res<InputBindings>,
+    time: Res<Time>,
+    mut widget_query: Query<(&mut ActionPromptWidget, &mut Visibility, &mut UiTransform)>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (mut widget, mut visibility, mut transform) in widget_query.iter_mut() {
+        let action = match widget.button {
+            InputAction::Secondary => prompts.secondary,
+            _ => prompts.jump,
+        };
+        if action != widget.shown {
+            widget.shown = action;
+            widget.since_changed = 0.0;
+            if let Ok(mut text) = text_query.get_mut(widget.label_text) {
+                text.0 = action
+                    .map(|action| action.label())
+                    .unwrap_or_default()
+                    .to_owned();
+            }
+        }
+        if (bindings.is_changed() || widget.since_changed == 0.0)
+            && let Ok(mut text) = text_query.get_mut(widget.key_text)
+        {
+            text.0 = bindings
+                .keys_for(widget.button)
+                .next()
+                .map(key_label)
+                .unwrap_or_default();
+        }
+        *visibility = match widget.shown {
+            Some(_) => Visibility::Inherited,
+            None => Visibility::Hidden,
+        };
+
+        // Pop out when the action changes, then settle back to normal size
+        widget.since_changed = (widget.since_changed + time.delta_secs()).min(PROMPT_POP_DURATION);
+        let remaining = 1.0 - widget.since_changed / PROMPT_POP_DURATION;
+        transform.scale = Vec2::splat(1.0 + (PROMPT_POP_SCALE - 1.0) * remaining * remaining);
+    }
+}
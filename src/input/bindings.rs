@@ -0,0 +1,422 @@
+use super::DEBUG_KEY;
+use bevy::{
+    prelude::*,
+    reflect::{DynamicEnum, DynamicVariant, FromReflect},
+};
+use std::path::PathBuf;
+
+/// Something the player can do, independent of which key or button does it. The move axis
+/// is made up of the four directional actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputAction {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Jump,
+    Secondary,
+    Pause,
+    Interact,
+}
+
+impl InputAction {
+    pub const ALL: [Self; 8] = [
+        Self::MoveLeft,
+        Self::MoveRight,
+        Self::MoveUp,
+        Self::MoveDown,
+        Self::Jump,
+        Self::Secondary,
+        Self::Pause,
+        Self::Interact,
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|action| format!("{:?}", action) == name)
+    }
+}
+
+/// A key that couldn't be bound because another action already uses it
+#[derive(Debug, Clone, Copy)]
+pub struct BindingConflict {
+    pub key: KeyCode,
+    pub bound_action: InputAction,
+}
+
+/// Which keys trigger each action. These are read from the user's config file at startup,
+/// and written back to it whenever an action is rebound. No key may trigger two actions.
+#[derive(Resource, Clone)]
+pub struct InputBindings {
+    bindings: Vec<(InputAction, KeyCode)>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self {
+            bindings: vec![
+                (InputAction::MoveLeft, KeyCode::ArrowLeft),
+                (InputAction::MoveLeft, KeyCode::KeyA),
+                (InputAction::MoveRight, KeyCode::ArrowRight),
+                (InputAction::MoveRight, KeyCode::KeyD),
+                (InputAction::MoveUp, KeyCode::ArrowUp),
+                (InputAction::MoveUp, KeyCode::KeyW),
+                (InputAction::MoveDown, KeyCode::ArrowDown),
+                (InputAction::MoveDown, KeyCode::KeyS),
+                (InputAction::Jump, KeyCode::Space),
+                (InputAction::Jump, KeyCode::KeyL),
+                (InputAction::Secondary, KeyCode::ShiftLeft),
+                (InputAction::Secondary, KeyCode::KeyK),
+                (InputAction::Pause, KeyCode::Escape),
+                (InputAction::Interact, KeyCode::KeyE),
+            ],
+        }
+    }
+}
+
+impl InputBindings {
+    pub fn keys_for(&self, action: InputAction) -> impl Iterator<Item = KeyCode> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(bound_action, _)| *bound_action == action)
+            .map(|(_, key)| *key)
+    }
+
+    pub fn action_for(&self, key: KeyCode) -> Option<InputAction> {
+        self.bindings
+            .iter()
+            .find(|(_, bound_key)| *bound_key == key)
+            .map(|(action, _)| *action)
+    }
+
+    pub fn pressed(&self, action: InputAction, keyboard_input: &ButtonInput<KeyCode>) -> bool {
+        keyboard_input.any_pressed(self.keys_for(action))
+    }
+
+    pub fn just_pressed(&self, action: InputAction, keyboard_input: &ButtonInput<KeyCode>) -> bool {
+        keyboard_input.any_just_pressed(self.keys_for(action))
+    }
+
+    /// Add a key to an action, unless the key already belongs to a different action
+    pub fn bind(&mut self, action: InputAction, key: KeyCode) -> Result<(), BindingConflict> {
+        match self.action_for(key) {
+            Some(bound_action) if bound_action == action => Ok(()),
+            Some(bound_action) => Err(BindingConflict { key, bound_action }),
+            None => {
+                self.bindings.push((action, key));
+                Ok(())
+            }
+        }
+    }
+
+    /// Make a key the only one for an action, unless the key already belongs to a different
+    /// action; the action keeps its old keys if so
+    pub fn rebind(&mut self, action: InputAction, key: KeyCode) -> Result<(), BindingConflict> {
+        if let Some(bound_action) = self.action_for(key)
+            && bound_action != action
+        {
+            return Err(BindingConflict { key, bound_action });
+        }
+        self.bindings
+            .retain(|(bound_action, _)| *bound_action != action);
+        self.bindings.push((action, key));
+        Ok(())
+    }
+
+    /// Where the user's bindings are kept, under the platform's usual config directory
+    pub fn config_path() -> PathBuf {
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .unwrap_or_default();
+        config_dir.join("graph-runner").join("input.cfg")
+    }
+
+    /// Read bindings in the form written by [`InputBindings::to_config`]. Lines that can't be
+    /// understood, and keys that conflict with an earlier line, are reported and skipped.
+    /// Actions missing from the file keep their default keys.
+    pub fn from_config(text: &str) -> Self {
+        let mut configured = Self { bindings: vec![] };
+        let mut configured_actions: Vec<InputAction> = vec![];
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((action_name, key_names)) = line.split_once('=') else {
+                println!("Input config line {} has no '='", line_index + 1);
+                continue;
+            };
+            let Some(action) = InputAction::from_name(action_name.trim()) else {
+                println!(
+                    "Input config line {} has unknown action {}",
+                    line_index + 1,
+                    action_name.trim()
+                );
+                continue;
+            };
+            configured_actions.push(action);
+            for key_name in key_names
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+            {
+                let Some(key) = parse_key_code(key_name) else {
+                    println!(
+                        "Input config line {} has unknown key {}",
+                        line_index + 1,
+                        key_name
+                    );
+                    continue;
+                };
+                if is_reserved(key) {
+                    println!(
+                        "Input config line {} binds {:?}, which the game already uses",
+                        line_index + 1,
+                        key
+                    );
+                    continue;
+                }
+                if let Err(conflict) = configured.bind(action, key) {
+                    println!(
+                        "Input config line {} binds {:?} to {:?}, but it's already bound to {:?}",
+                        line_index + 1,
+                        key,
+                        action,
+                        conflict.bound_action
+                    );
+                }
+            }
+        }
+
+        // Fill in any actions the file doesn't mention, as long as their keys are still free
+        let defaults = Self::default();
+        for (action, key) in defaults.bindings {
+            if !configured_actions.contains(&action) {
+                let _ = configured.bind(action, key);
+            }
+        }
+        configured
+    }
+
+    pub fn to_config(&self) -> String {
+        let mut text = String::from("# Action = key, key, ...\n");
+        for action in InputAction::ALL {
+            let key_names: Vec<String> = self
+                .keys_for(action)
+                .map(|key| format!("{:?}", key))
+                .collect();
+            text.push_str(&format!("{:?} = {}\n", action, key_names.join(", ")));
+        }
+        text
+    }
+
+    /// Load the user's bindings, or the defaults if there's no config file yet
+    pub fn load() -> Self {
+        let path = Self::config_path();
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                println!("Loaded input bindings from {}", path.display());
+                Self::from_config(&text)
+            }
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), BevyError> {
+        let path = Self::config_path();
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(&path, self.to_config())?;
+        println!("Saved input bindings to {}", path.display());
+        Ok(())
+    }
+}
+
+/// Key codes are named in the config file as they are in [`KeyCode`], e.g. KeyA or Space
+fn parse_key_code(name: &str) -> Option<KeyCode> {
+    KeyCode::from_reflect(&DynamicEnum::new(name.to_string(), DynamicVariant::Unit))
+}
+
+/// Ask for the next key pressed to become the only key for an action. Keys that already
+/// belong to another action are refused, and the next key after that is tried instead.
+#[derive(Message, Debug, Clone, Copy)]
+pub struct RebindAction(pub InputAction);
+
+/// The action waiting for a key from [`RebindAction`], during which no actions trigger
+#[derive(Resource, Default)]
+pub struct PendingRebind(pub Option<InputAction>);
+
+/// Function keys that ask to rebind each action, in the order of [`InputAction::ALL`]. They
+/// pick what to rebind rather than being bindable themselves.
+const REBIND_KEYS: [KeyCode; 8] = [
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+];
+
+/// Keys the game itself uses, which can't be bound to an action
+fn is_reserved(key: KeyCode) -> bool {
+    REBIND_KEYS.contains(&key) || key == DEBUG_KEY
+}
+
+/// There's no settings menu yet, so a function key asks to rebind its action
+pub fn request_rebinding(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut messages: MessageWriter<RebindAction>,
+) {
+    for (key, action) in REBIND_KEYS.into_iter().zip(InputAction::ALL) {
+        if keyboard_input.just_pressed(key) {
+            messages.write(RebindAction(action));
+        }
+    }
+}
+
+pub fn start_rebinding(
+    mut messages: MessageReader<RebindAction>,
+    mut pending_rebind: ResMut<PendingRebind>,
+) {
+    if let Some(RebindAction(action)) = messages.read().last() {
+        println!("Press a key to bind to {:?}", action);
+        pending_rebind.0 = Some(*action);
+    }
+}
+
+/// Bind the next key pressed to the pending action, saving the bindings if it works. The new
+/// binding is kept for this session even if it can't be saved.
+pub fn capture_rebinding(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut pending_rebind: ResMut<PendingRebind>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    let Some(action) = pending_rebind.0 else {
+        return;
+    };
+    // The function keys choose which action to rebind instead
+    for key in keyboard_input
+        .get_just_pressed()
+        .filter(|key| !REBIND_KEYS.contains(*key))
+    {
+        if is_reserved(*key) {
+            println!(
+                "Can't bind {:?} to {:?}; the game already uses it",
+                key, action
+            );
+            continue;
+        }
+        match bindings.rebind(action, *key) {
+            Ok(()) => {
+                println!("Bound {:?} to {:?}", key, action);
+                pending_rebind.0 = None;
+                if let Err(error) = bindings.save() {
+                    eprintln!("Could not save input bindings: {}", error);
+                }
+                return;
+            }
+            Err(conflict) => {
+                println!(
+                    "Can't bind {:?} to {:?}; it's already bound to {:?}",
+                    conflict.key, action, conflict.bound_action
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(bindings: &InputBindings, action: InputAction) -> Vec<KeyCode> {
+        bindings.keys_for(action).collect()
+    }
+
+    #[test]
+    fn from_config_skips_unknown_actions() {
+        let bindings = InputBindings::from_config("Fly = KeyF\nJump = KeyJ\n");
+        assert_eq!(bindings.action_for(KeyCode::KeyF), None);
+        assert_eq!(keys(&bindings, InputAction::Jump), vec![KeyCode::KeyJ]);
+    }
+
+    #[test]
+    fn from_config_skips_unknown_keys_but_keeps_the_rest_of_the_line() {
+        let bindings = InputBindings::from_config("Jump = NotAKey, KeyJ\n");
+        assert_eq!(keys(&bindings, InputAction::Jump), vec![KeyCode::KeyJ]);
+    }
+
+    #[test]
+    fn from_config_refuses_a_key_already_bound_by_an_earlier_line() {
+        let bindings = InputBindings::from_config("Jump = KeyJ\nSecondary = KeyJ, KeyK\n");
+        assert_eq!(bindings.action_for(KeyCode::KeyJ), Some(InputAction::Jump));
+        assert_eq!(keys(&bindings, InputAction::Secondary), vec![KeyCode::KeyK]);
+    }
+
+    #[test]
+    fn from_config_skips_keys_the_game_already_uses() {
+        let bindings = InputBindings::from_config("Jump = F1, KeyB, KeyJ\n");
+        assert_eq!(keys(&bindings, InputAction::Jump), vec![KeyCode::KeyJ]);
+        assert_eq!(bindings.action_for(KeyCode::F1), None);
+        assert_eq!(bindings.action_for(DEBUG_KEY), None);
+    }
+
+    #[test]
+    fn from_config_fills_in_defaults_for_missing_actions() {
+        let bindings = InputBindings::from_config("# Just one\nJump = KeyA\n");
+        assert_eq!(keys(&bindings, InputAction::Jump), vec![KeyCode::KeyA]);
+        assert_eq!(keys(&bindings, InputAction::Interact), vec![KeyCode::KeyE]);
+        // KeyA is taken by the configured line, so the default can't have it
+        assert_eq!(
+            keys(&bindings, InputAction::MoveLeft),
+            vec![KeyCode::ArrowLeft]
+        );
+    }
+
+    #[test]
+    fn rebind_refuses_a_key_bound_to_another_action() {
+        let mut bindings = InputBindings::default();
+        let conflict = bindings
+            .rebind(InputAction::Jump, KeyCode::KeyA)
+            .unwrap_err();
+        assert_eq!(conflict.key, KeyCode::KeyA);
+        assert_eq!(conflict.bound_action, InputAction::MoveLeft);
+        assert_eq!(
+            keys(&bindings, InputAction::Jump),
+            vec![KeyCode::Space, KeyCode::KeyL]
+        );
+        assert_eq!(
+            bindings.action_for(KeyCode::KeyA),
+            Some(InputAction::MoveLeft)
+        );
+    }
+
+    #[test]
+    fn rebind_replaces_the_action_keys() {
+        let mut bindings = InputBindings::default();
+        bindings.rebind(InputAction::Jump, KeyCode::KeyJ).unwrap();
+        assert_eq!(keys(&bindings, InputAction::Jump), vec![KeyCode::KeyJ]);
+        assert_eq!(bindings.action_for(KeyCode::Space), None);
+    }
+
+    #[test]
+    fn config_round_trips() {
+        let mut bindings = InputBindings::default();
+        bindings.rebind(InputAction::Jump, KeyCode::KeyJ).unwrap();
+        bindings.bind(InputAction::Jump, KeyCode::Enter).unwrap();
+        bindings
+            .rebind(InputAction::Interact, KeyCode::Tab)
+            .unwrap();
+
+        let reloaded = InputBindings::from_config(&bindings.to_config());
+        for action in InputAction::ALL {
+            assert_eq!(keys(&reloaded, action), keys(&bindings, action));
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use super::{ActionState, InputAction, bindings::PendingRebind};
+use bevy::{
+    input::gamepad::{GamepadConnection, GamepadConnectionEvent},
+    prelude::*,
+};
+
+/// Radial deadzones applied to the left stick, on top of Bevy's per-axis filtering. Tilt
+/// inside the inner radius reads as none, tilt past the outer radius reads as full, and tilt
+/// in between is rescaled to cover the whole range so that slow walking stays reachable.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct GamepadDeadzones {
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+}
+
+impl Default for GamepadDeadzones {
+    fn default() -> Self {
+        Self {
+            inner_radius: 0.15,
+            outer_radius: 0.95,
+        }
+    }
+}
+
+impl GamepadDeadzones {
+    pub fn apply(&self, stick: Vec2) -> Vec2 {
+        let tilt = stick.length();
+        if tilt <= self.inner_radius {
+            return Vec2::ZERO;
+        }
+        let range = (self.outer_radius - self.inner_radius).max(f32::EPSILON);
+        let scaled_tilt = ((tilt - self.inner_radius) / range).min(1.0);
+        stick * (scaled_tilt / tilt)
+    }
+}
+
+/// The buttons that trigger each action on every gamepad
+const BUTTON_ACTIONS: [(GamepadButton, InputAction); 8] = [
+    (GamepadButton::DPadLeft, InputAction::MoveLeft),
+    (GamepadButton::DPadRight, InputAction::MoveRight),
+    (GamepadButton::DPadUp, InputAction::MoveUp),
+    (GamepadButton::DPadDown, InputAction::MoveDown),
+    (GamepadButton::South, InputAction::Jump),
+    (GamepadButton::West, InputAction::Secondary),
+    (GamepadButton::Start, InputAction::Pause),
+    (GamepadButton::North, InputAction::Interact),
+];
+
+/// Report gamepads coming and going. Nothing else is needed for hot-plugging, since
+/// [`read_gamepad_actions`] looks at whichever gamepad entities exist each frame.
+pub fn log_gamepad_connections(mut connection_messages: MessageReader<GamepadConnectionEvent>) {
+    for message in connection_messages.read() {
+        match &message.connection {
+            GamepadConnection::Connected { name, .. } => {
+                println!("Gamepad {} connected: {}", message.gamepad, name);
+            }
+            GamepadConnection::Disconnected => {
+                println!("Gamepad {} disconnected", message.gamepad);
+            }
+        }
+    }
+}
+
+/// Add every connected gamepad's input to this frame's actions. Buttons from any pad count,
+/// and the most-tilted stick (or d-pad) steers if it's tilted further than the keyboard.
+/// Since this only reads [`Gamepad`] components, writing Bevy's raw gamepad messages (a
+/// connection, then button and axis changes) drives it exactly as a real pad would.
+pub fn read_gamepad_actions(
+    gamepads: Query<&Gamepad>,
+    deadzones: Res<GamepadDeadzones>,
+    pending_rebind: Res<PendingRebind>,
+    mut action_state: ResMut<ActionState>,
+) {
+    if pending_rebind.0.is_some() {
+        return;
+    }
+    for gamepad in gamepads.iter() {
+        for (button, action) in BUTTON_ACTIONS {
+            if gamepad.pressed(button) {
+                action_state.press(action, gamepad.just_pressed(button));
+            }
+        }
+
+        let stick = deadzones.apply(gamepad.left_stick());
+        let steering = match stick.length() >= gamepad.dpad().length() {
+            true => stick,
+            false => gamepad.dpad(),
+        };
+        if steering.length() > action_state.move_axis.length() {
+            action_state.move_axis = steering;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::input::{
+        InputPlugin,
+        gamepad::{
+            AxisSettings, GamepadConnectionEvent, GamepadSettings, RawGamepadAxisChangedEvent,
+            RawGamepadButtonChangedEvent, RawGamepadEvent,
+        },
+    };
+
+    fn clear_action_state(mut action_state: ResMut<ActionState>) {
+        *action_state = ActionState::default();
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(InputPlugin)
+            .init_resource::<ActionState>()
+            .init_resource::<GamepadDeadzones>()
+            .init_resource::<PendingRebind>()
+            .add_systems(Update, (clear_action_state, read_gamepad_actions).chain());
+        app
+    }
+
+    /// Plug in a gamepad the way the platform backend would. Bevy's own per-axis filtering
+    /// is turned off so that only the radial deadzones shape the stick.
+    fn connect(app: &mut App) -> Entity {
+        let unfiltered_axes = AxisSettings::new(-1.0, 0.0, 0.0, 1.0, 0.0).unwrap();
+        let gamepad = app
+            .world_mut()
+            .spawn(GamepadSettings {
+                default_axis_settings: unfiltered_axes,
+                ..default()
+            })
+            .id();
+        app.world_mut()
+            .write_message(RawGamepadEvent::Connection(GamepadConnectionEvent::new(
+                gamepad,
+                GamepadConnection::Connected {
+                    name: "Test pad".into(),
+                    vendor_id: None,
+                    product_id: None,
+                },
+            )));
+        // The connection is passed on as a processed message, then picked up a frame later
+        app.update();
+        app.update();
+        assert!(app.world().get::<Gamepad>(gamepad).is_some());
+        gamepad
+    }
+
+    fn disconnect(app: &mut App, gamepad: Entity) {
+        app.world_mut()
+            .write_message(RawGamepadEvent::Connection(GamepadConnectionEvent::new(
+                gamepad,
+                GamepadConnection::Disconnected,
+            )));
+        app.update();
+        app.update();
+        assert!(app.world().get::<Gamepad>(gamepad).is_none());
+    }
+
+    fn tilt_stick(app: &mut App, gamepad: Entity, stick: Vec2) {
+        app.world_mut()
+            .write_message(RawGamepadEvent::Axis(RawGamepadAxisChangedEvent::new(
+                gamepad,
+                GamepadAxis::LeftStickX,
+                stick.x,
+            )));
+        app.world_mut()
+            .write_message(RawGamepadEvent::Axis(RawGamepadAxisChangedEvent::new(
+                gamepad,
+                GamepadAxis::LeftStickY,
+                stick.y,
+            )));
+    }
+
+    fn set_button(app: &mut App, gamepad: Entity, button: GamepadButton, value: f32) {
+        app.world_mut()
+            .write_message(RawGamepadEvent::Button(RawGamepadButtonChangedEvent::new(
+                gamepad, button, value,
+            )));
+    }
+
+    fn move_axis_after_update(app: &mut App) -> Vec2 {
+        app.update();
+        app.world().resource::<ActionState>().move_axis
+    }
+
+    #[test]
+    fn stick_inside_inner_deadzone_reads_as_none() {
+        let mut app = app();
+        let gamepad = connect(&mut app);
+        tilt_stick(&mut app, gamepad, Vec2::new(0.1, 0.05));
+        assert_eq!(move_axis_after_update(&mut app), Vec2::ZERO);
+    }
+
+    #[test]
+    fn stick_between_deadzones_is_rescaled_keeping_direction() {
+        let mut app = app();
+        let gamepad = connect(&mut app);
+        // Tilted by 0.5, which is 0.35 / 0.8 of the way from the inner radius to the outer
+        tilt_stick(&mut app, gamepad, Vec2::new(0.3, 0.4));
+        let move_axis = move_axis_after_update(&mut app);
+        assert!((move_axis.length() - 0.4375).abs() < 1e-4);
+        assert!((move_axis.normalize() - Vec2::new(0.6, 0.8)).length() < 1e-4);
+    }
+
+    #[test]
+    fn stick_past_outer_deadzone_reads_as_full() {
+        let mut app = app();
+        let gamepad = connect(&mut app);
+        tilt_stick(&mut app, gamepad, Vec2::new(-0.98, 0.0));
+        let move_axis = move_axis_after_update(&mut app);
+        assert!((move_axis - Vec2::NEG_X).length() < 1e-4);
+    }
+
+    #[test]
+    fn button_presses_trigger_their_actions() {
+        let mut app = app();
+        let gamepad = connect(&mut app);
+        set_button(&mut app, gamepad, GamepadButton::South, 1.0);
+        app.update();
+        let action_state = app.world().resource::<ActionState>();
+        assert!(action_state.pressed(InputAction::Jump));
+        assert!(action_state.just_pressed(InputAction::Jump));
+        assert!(!action_state.pressed(InputAction::Secondary));
+
+        app.update();
+        let action_state = app.world().resource::<ActionState>();
+        assert!(action_state.pressed(InputAction::Jump));
+        assert!(!action_state.just_pressed(InputAction::Jump));
+
+        set_button(&mut app, gamepad, GamepadButton::South, 0.0);
+        app.update();
+        assert!(
+            !app.world()
+                .resource::<ActionState>()
+                .pressed(InputAction::Jump)
+        );
+    }
+
+    #[test]
+    fn dpad_steers_when_tilted_further_than_the_stick() {
+        let mut app = app();
+        let gamepad = connect(&mut app);
+        set_button(&mut app, gamepad, GamepadButton::DPadUp, 1.0);
+        tilt_stick(&mut app, gamepad, Vec2::new(0.5, 0.0));
+        let move_axis = move_axis_after_update(&mut app);
+        assert!((move_axis - Vec2::Y).length() < 1e-4);
+        assert!(
+            app.world()
+                .resource::<ActionState>()
+                .pressed(InputAction::MoveUp)
+        );
+    }
+
+    #[test]
+    fn most_tilted_pad_steers_until_it_is_unplugged() {
+        let mut app = app();
+        let first = connect(&mut app);
+        let second = connect(&mut app);
+        tilt_stick(&mut app, first, Vec2::new(0.55, 0.0));
+        tilt_stick(&mut app, second, Vec2::new(0.0, -0.95));
+        let move_axis = move_axis_after_update(&mut app);
+        assert!((move_axis - Vec2::NEG_Y).length() < 1e-4);
+
+        set_button(&mut app, second, GamepadButton::West, 1.0);
+        app.update();
+        assert!(
+            app.world()
+                .resource::<ActionState>()
+                .pressed(InputAction::Secondary)
+        );
+
+        disconnect(&mut app, second);
+        let move_axis = move_axis_after_update(&mut app);
+        assert!((move_axis - Vec2::new(0.5, 0.0)).length() < 1e-4);
+        assert!(
+            !app.world()
+                .resource::<ActionState>()
+                .pressed(InputAction::Secondary)
+        );
+    }
+}
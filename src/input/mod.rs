@@ -0,0 +1,164 @@
+mod bindings;
+mod gamepad;
+mod touch;
+
+pub use bindings::{InputAction, InputBindings, RebindAction};
+pub use gamepad::GamepadDeadzones;
+pub use touch::TouchControlsLayout;
+
+use crate::{
+    InputSystems,
+    controller::{MovementIntent, PlayerController},
+    state::AppState,
+};
+use bevy::prelude::*;
+use bindings::{PendingRebind, capture_rebinding, request_rebinding, start_rebinding};
+use gamepad::{log_gamepad_connections, read_gamepad_actions};
+use touch::{
+    TouchJoystick, read_touch_actions, remove_touch_controls, spawn_touch_controls,
+    update_touch_control_nodes,
+};
+
+/// Key that triggers [`DebugPressed`] in debug builds, which can't be bound to an action
+const DEBUG_KEY: KeyCode = KeyCode::KeyB;
+
+/// The actions active this frame, gathered from whatever devices are bound to them
+#[derive(Resource, Default)]
+pub struct ActionState {
+    pub move_axis: Vec2,
+    pressed: Vec<InputAction>,
+    just_pressed: Vec<InputAction>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: InputAction) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: InputAction) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn press(&mut self, action: InputAction, just_pressed: bool) {
+        if !self.pressed.contains(&action) {
+            self.pressed.push(action);
+        }
+        if just_pressed && !self.just_pressed.contains(&action) {
+            self.just_pressed.push(action);
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+#[derive(Event)]
+pub struct DebugPressed;
+
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<RebindAction>()
+            .insert_resource(InputBindings::load())
+            .init_resource::<PendingRebind>()
+            .init_resource::<ActionState>()
+            .init_resource::<GamepadDeadzones>()
+            .init_resource::<TouchControlsLayout>()
+            .init_resource::<TouchJoystick>()
+            .add_systems(OnEnter(AppState::Game), spawn_touch_controls)
+            .add_systems(OnExit(AppState::Game), remove_touch_controls)
+            .add_systems(
+                Update,
+                update_touch_control_nodes.run_if(in_state(AppState::Game)),
+            )
+            .add_systems(
+                PreUpdate,
+                (
+                    request_rebinding,
+                    start_rebinding,
+                    log_gamepad_connections,
+                    read_keyboard_actions,
+                    read_gamepad_actions,
+                    read_touch_actions,
+                    capture_rebinding,
+                    apply_actions.run_if(in_state(AppState::Game)),
+                )
+                    .chain()
+                    .in_set(InputSystems::PollInputs),
+            );
+    }
+}
+
+/// Start each frame's actions afresh from the keyboard. Nothing triggers while waiting for a
+/// key to rebind, so that the key pressed doesn't also do whatever it was bound to.
+fn read_keyboard_actions(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<InputBindings>,
+    pending_rebind: Res<PendingRebind>,
+    mut action_state: ResMut<ActionState>,
+) {
+    *action_state = ActionState::default();
+    if pending_rebind.0.is_some() {
+        return;
+    }
+    for action in InputAction::ALL {
+        if bindings.pressed(action, &keyboard_input) {
+            action_state.press(action, bindings.just_pressed(action, &keyboard_input));
+        }
+    }
+
+    let axis_value = |negative: InputAction, positive: InputAction| match (
+        action_state.pressed(negative),
+        action_state.pressed(positive),
+    ) {
+        (true, false) => -1.0,
+        (false, true) => 1.0,
+        _ => 0.0,
+    };
+    let move_axis = Vec2::new(
+        axis_value(InputAction::MoveLeft, InputAction::MoveRight),
+        axis_value(InputAction::MoveDown, InputAction::MoveUp),
+    );
+    action_state.move_axis = move_axis;
+}
+
+fn apply_actions(
+    #[cfg(debug_assertions)] mut commands: Commands,
+    #[cfg(debug_assertions)] keyboard_input: Res<ButtonInput<KeyCode>>,
+    action_state: Res<ActionState>,
+    mut intent_query: Query<&mut MovementIntent, With<PlayerController>>,
+    mut exit_signal: MessageWriter<AppExit>,
+) {
+    // There's no pause menu yet, so pausing quits
+    if action_state.just_pressed(InputAction::Pause) {
+        exit_signal.write(AppExit::Success);
+        return;
+    }
+
+    #[cfg(debug_assertions)]
+    if keyboard_input.just_pressed(DEBUG_KEY) {
+        commands.trigger(DebugPressed);
+    }
+
+    let Ok(mut input_state) = intent_query.single_mut() else {
+        return;
+    };
+
+    let was_previously_pressing_jump = input_state.pressing_jump;
+    let was_previously_pressing_secondary = input_state.pressing_secondary;
+    let latched_jump = input_state.just_pressed_jump;
+    let latched_secondary = input_state.just_pressed_secondary;
+    *input_state = MovementIntent::default();
+
+    input_state.input_direction_x = action_state.move_axis.x;
+    input_state.input_direction_y = action_state.move_axis.y;
+
+    let now_pressing_jump = action_state.pressed(InputAction::Jump);
+    input_state.pressing_jump = now_pressing_jump;
+    input_state.just_pressed_jump =
+        latched_jump || (now_pressing_jump && !was_previously_pressing_jump);
+
+    let now_pressing_secondary = action_state.pressed(InputAction::Secondary);
+    input_state.pressing_secondary = now_pressing_secondary;
+    input_state.just_pressed_secondary =
+        latched_secondary || (now_pressing_secondary && !was_previously_pressing_secondary);
+}
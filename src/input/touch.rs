@@ -0,0 +1,288 @@
+use super::{ActionState, InputAction, bindings::PendingRebind};
+use crate::app_draw_layer;
+use bevy::{camera::visibility::RenderLayers, prelude::*, window::PrimaryWindow};
+
+/// Where the on-screen controls sit and how they look. Offsets are in logical pixels, from
+/// the bottom-left corner of the window to the joystick's centre, and from the bottom-right
+/// corner to each button's centre. Changes apply immediately, e.g. from the inspector.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct TouchControlsLayout {
+    pub enabled: bool,
+    pub opacity: f32,
+    pub joystick_offset: Vec2,
+    pub joystick_radius: f32,
+    pub knob_radius: f32,
+    pub jump_button_offset: Vec2,
+    pub secondary_button_offset: Vec2,
+    pub button_radius: f32,
+}
+
+impl Default for TouchControlsLayout {
+    fn default() -> Self {
+        Self {
+            enabled: cfg!(target_os = "android"),
+            opacity: 0.4,
+            joystick_offset: Vec2::new(140.0, 140.0),
+            joystick_radius: 90.0,
+            knob_radius: 36.0,
+            jump_button_offset: Vec2::new(90.0, 110.0),
+            secondary_button_offset: Vec2::new(200.0, 70.0),
+            button_radius: 44.0,
+        }
+    }
+}
+
+impl TouchControlsLayout {
+    fn joystick_centre(&self, window_size: Vec2) -> Vec2 {
+        Vec2::new(
+            self.joystick_offset.x,
+            window_size.y - self.joystick_offset.y,
+        )
+    }
+
+    fn button_offset(&self, action: InputAction) -> Vec2 {
+        match action {
+            InputAction::Secondary => self.secondary_button_offset,
+            _ => self.jump_button_offset,
+        }
+    }
+
+    fn button_centre(&self, action: InputAction, window_size: Vec2) -> Vec2 {
+        window_size - self.button_offset(action)
+    }
+}
+
+/// Something pressing on the screen; the left mouse button stands in for a finger so that
+/// the controls can be tried out on desktop
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pointer {
+    Touch(u64),
+    Mouse,
+}
+
+/// Which pointer is holding the joystick, and how far it's pushed it (in window coordinates,
+/// relative to the joystick's centre)
+#[derive(Resource, Default)]
+pub struct TouchJoystick {
+    pointer: Option<Pointer>,
+    displacement: Vec2,
+}
+
+#[derive(Component)]
+pub struct TouchControlsRoot;
+
+#[derive(Component)]
+pub struct TouchJoystickBase;
+
+#[derive(Component)]
+pub struct TouchJoystickKnob;
+
+#[derive(Component)]
+pub struct TouchButton(InputAction);
+
+const TOUCH_BUTTON_ACTIONS: [InputAction; 2] = [InputAction::Jump, InputAction::Secondary];
+
+pub fn spawn_touch_controls(mut commands: Commands) {
+    commands
+        .spawn((
+            TouchControlsRoot,
+            RenderLayers::layer(app_draw_layer::HUD),
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            Pickable::IGNORE,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    TouchJoystickBase,
+                    Node {
+                        position_type: PositionType::Absolute,
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BorderRadius::MAX,
+                    BackgroundColor(Color::WHITE),
+                ))
+                .with_child((
+                    TouchJoystickKnob,
+                    Node::default(),
+                    BorderRadius::MAX,
+                    BackgroundColor(Color::BLACK),
+                ));
+            for action in TOUCH_BUTTON_ACTIONS {
+                parent.spawn((
+                    TouchButton(action),
+                    Node {
+                        position_type: PositionType::Absolute,
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BorderRadius::MAX,
+                    BackgroundColor(Color::WHITE),
+                    children![(
+                        Text(format!("{:?}", action)),
+                        TextFont {
+                            font_size: 16.0,
+                            ..default()
+                        },
+                        TextColor(Color::BLACK),
+                    )],
+                ));
+            }
+        });
+}
+
+pub fn remove_touch_controls(
+    mut commands: Commands,
+    root_query: Query<Entity, With<TouchControlsRoot>>,
+    mut joystick: ResMut<TouchJoystick>,
+) {
+    for root in root_query.iter() {
+        commands.entity(root).despawn();
+    }
+    *joystick = TouchJoystick::default();
+}
+
+/// Add presses on the on-screen joystick and buttons to this frame's actions. A finger that
+/// lands on the joystick keeps steering it wherever it drags until lifted; the buttons are
+/// pressed by any finger currently over them.
+pub fn read_touch_actions(
+    touches: Res<Touches>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    layout: Res<TouchControlsLayout>,
+    pending_rebind: Res<PendingRebind>,
+    mut joystick: ResMut<TouchJoystick>,
+    mut action_state: ResMut<ActionState>,
+) {
+    let Ok(window) = window_query.single() else {
+        return;
+    };
+    if !layout.enabled || pending_rebind.0.is_some() {
+        *joystick = TouchJoystick::default();
+        return;
+    }
+
+    // (pointer, position, just pressed)
+    let mut pointers: Vec<(Pointer, Vec2, bool)> = touches
+        .iter()
+        .map(|touch| {
+            (
+                Pointer::Touch(touch.id()),
+                touch.position(),
+                touches.just_pressed(touch.id()),
+            )
+        })
+        .collect();
+    if mouse_input.pressed(MouseButton::Left)
+        && let Some(cursor_position) = window.cursor_position()
+    {
+        pointers.push((
+            Pointer::Mouse,
+            cursor_position,
+            mouse_input.just_pressed(MouseButton::Left),
+        ));
+    }
+
+    let window_size = window.size();
+    let joystick_centre = layout.joystick_centre(window_size);
+    let held_pointer = joystick
+        .pointer
+        .and_then(|held| pointers.iter().find(|(pointer, _, _)| *pointer == held));
+    let steering_pointer = held_pointer.or_else(|| {
+        pointers.iter().find(|(_, position, just_pressed)| {
+            *just_pressed && position.distance(joystick_centre) <= layout.joystick_radius
+        })
+    });
+    match steering_pointer {
+        Some((pointer, position, _)) => {
+            joystick.pointer = Some(*pointer);
+            joystick.displacement =
+                (*position - joystick_centre).clamp_length_max(layout.joystick_radius);
+            let steering = Vec2::new(joystick.displacement.x, -joystick.displacement.y)
+                / layout.joystick_radius.max(f32::EPSILON);
+            if steering.length() > action_state.move_axis.length() {
+                action_state.move_axis = steering;
+            }
+        }
+        None => {
+            *joystick = TouchJoystick::default();
+        }
+    }
+
+    for action in TOUCH_BUTTON_ACTIONS {
+        let button_centre = layout.button_centre(action, window_size);
+        for (pointer, position, just_pressed) in pointers.iter() {
+            if joystick.pointer == Some(*pointer) {
+                continue;
+            }
+            if position.distance(button_centre) <= layout.button_radius {
+                action_state.press(action, *just_pressed);
+            }
+        }
+    }
+}
+
+/// Keep the drawn controls in line with the layout, and show where the joystick is pushed
+pub fn update_touch_control_nodes(
+    layout: Res<TouchControlsLayout>,
+    joystick: Res<TouchJoystick>,
+    action_state: Res<ActionState>,
+    mut root_query: Query<&mut Visibility, With<TouchControlsRoot>>,
+    mut base_query: Query<
+        (&mut Node, &mut BackgroundColor),
+        (
+            With<TouchJoystickBase>,
+            Without<TouchJoystickKnob>,
+            Without<TouchButton>,
+        ),
+    >,
+    mut knob_query: Query<
+        (&mut Node, &mut BackgroundColor),
+        (With<TouchJoystickKnob>, Without<TouchButton>),
+    >,
+    mut button_query: Query<(&TouchButton, &mut Node, &mut BackgroundColor)>,
+) {
+    for mut visibility in root_query.iter_mut() {
+        *visibility = match layout.enabled {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+    }
+    for (mut node, mut background) in base_query.iter_mut() {
+        let diameter = 2.0 * layout.joystick_radius;
+        node.width = Val::Px(diameter);
+        node.height = Val::Px(diameter);
+        node.left = Val::Px(layout.joystick_offset.x - layout.joystick_radius);
+        node.bottom = Val::Px(layout.joystick_offset.y - layout.joystick_radius);
+        background.0 = Color::WHITE.with_alpha(layout.opacity);
+    }
+    for (mut node, mut background) in knob_query.iter_mut() {
+        let diameter = 2.0 * layout.knob_radius;
+        node.width = Val::Px(diameter);
+        node.height = Val::Px(diameter);
+        node.left = Val::Px(joystick.displacement.x);
+        node.top = Val::Px(joystick.displacement.y);
+        background.0 = Color::BLACK.with_alpha(layout.opacity);
+    }
+    for (button, mut node, mut background) in button_query.iter_mut() {
+        let offset = layout.button_offset(button.0);
+        let diameter = 2.0 * layout.button_radius;
+        node.width = Val::Px(diameter);
+        node.height = Val::Px(diameter);
+        node.right = Val::Px(offset.x - layout.button_radius);
+        node.bottom = Val::Px(offset.y - layout.button_radius);
+        let alpha = match action_state.pressed(button.0) {
+            true => (2.0 * layout.opacity).min(1.0),
+            false => layout.opacity,
+        };
+        background.0 = Color::WHITE.with_alpha(alpha);
+    }
+}
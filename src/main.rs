@@ -1,15 +1,20 @@
 mod animation;
+mod breakable;
 mod camera;
 mod controller;
+mod hud;
 mod input;
 mod inspector;
 mod lighting;
 mod loading;
 mod markers;
 mod mood;
+mod music;
+mod platform;
 mod scene;
 mod splash;
 mod state;
+mod tide;
 
 use avian3d::prelude::*;
 use bevy::prelude::*;
@@ -31,6 +36,14 @@ enum InputSystems {
 }
 
 fn main() {
+    if let Some(render_args) = music::OfflineRenderArgs::from_env() {
+        if let Err(error) = music::render_offline(&render_args) {
+            eprintln!("Offline music render failed: {}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     App::new()
         .insert_resource(ClearColor(Color::srgb(0.2, 0.2, 0.2)))
         .add_plugins((
@@ -38,18 +51,25 @@ fn main() {
             inspector::InspectorPlugin,
             PhysicsPlugins::default(),
             SkeinPlugin::default(),
+        ))
+        .add_plugins((
             state::StatePlugin,
             animation::AnimationPlugin,
+            breakable::BreakablePlugin,
             camera::GameCameraPlugin,
             scene::ScenePlugin,
+            hud::HudPlugin,
             input::InputPlugin,
             controller::CharacterControllerPlugin,
             lighting::LightingPlugin,
             loading::LoadingPlugin,
             markers::MarkerPlugin,
             mood::MoodPlugin,
+            platform::PlatformPlugin,
             splash::SplashPlugin,
+            tide::TidePlugin,
         ))
+        .add_plugins(music::MusicPlugin)
         .configure_sets(
             PreUpdate,
             (
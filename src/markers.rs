@@ -33,6 +33,14 @@ pub enum CameraFocus {
     Proximity { distance: f32 },
 }
 
+/// A power-up waiting in the level to be picked up. Once collected, the
+/// component is moved onto the player, who holds it from then on.
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub enum PowerUp {
+    GlideSuit,
+}
+
 #[derive(Component, Reflect)]
 #[reflect(Component)]
 #[component(storage = "SparseSet")]
@@ -46,9 +54,88 @@ pub struct Grounded {
 #[component(on_add = on_water_volume_added)]
 pub struct WaterVolume;
 
+/// A volume which changes how the player moves while they overlap it, such as
+/// a swamp or mud. Each field scales or overrides the matching movement factor
+/// for whatever state the player is in; gravity applies to the vertical
+/// acceleration of that state.
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component, Default)]
+#[component(on_add = on_movement_modifier_volume_added)]
+pub struct MovementModifierVolume {
+    pub input_factor: MovementModifier,
+    pub reverse_input_factor: MovementModifier,
+    pub jump_factor: MovementModifier,
+    pub max_speed_factor: MovementModifier,
+    pub stop_factor: MovementModifier,
+    pub gravity: MovementModifier,
+}
+
+#[derive(Reflect, Debug, Clone, Copy)]
+pub enum MovementModifier {
+    Scale(f32),
+    Override(f32),
+}
+
+impl Default for MovementModifier {
+    fn default() -> Self {
+        Self::Scale(1.0)
+    }
+}
+
+impl MovementModifier {
+    pub fn apply(&self, value: f32) -> f32 {
+        match self {
+            Self::Scale(scale) => scale * value,
+            Self::Override(override_value) => *override_value,
+        }
+    }
+}
+
+/// A box-shaped area of the level where the camera is framed differently, authored like
+/// the other volumes and hidden once added. While the player is inside it, the camera's
+/// position is kept within any of the clamps given, held at the rail height if there is one,
+/// and held at the zoom distance if there is one. The region takes effect gradually over the
+/// blend distance in from its edges, and regions inside other regions take precedence.
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component, Default)]
+#[component(on_add = on_camera_region_added)]
+pub struct CameraRegion {
+    pub min_x: Option<f32>,
+    pub max_x: Option<f32>,
+    pub min_y: Option<f32>,
+    pub max_y: Option<f32>,
+    pub rail_y: Option<f32>,
+    pub distance: Option<f32>,
+    pub blend_distance: f32,
+}
+
+/// Something chasing the player, such as the Activist. The closer it gets, the more tense
+/// the music becomes.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Chaser;
+
+/// A box-shaped area of the level that counts as reaching a checkpoint while the player
+/// is inside it, authored like the other volumes and hidden once added. Tension builds the
+/// longer it's been since the player was last in one.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+#[component(on_add = on_checkpoint_added)]
+pub struct Checkpoint;
+
+/// Something the player carries that can be damaged, such as the artwork. The more damaged
+/// it is, the more tense the music becomes.
 #[derive(Component, Reflect, Debug)]
 #[reflect(Component)]
-pub struct WaterVolumeExtents {
+pub struct CarriedItem {
+    pub health: f32,
+    pub max_health: f32,
+}
+
+/// Half-extents of a box-shaped volume, measured from its mesh when added
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct VolumeExtents {
     pub half_extent_x: f32,
     pub half_extent_y: f32,
     pub half_extent_z: f32,
@@ -59,6 +146,24 @@ pub struct WaterVolumeExtents {
 #[component(on_add = on_trimesh_added)]
 pub struct Trimesh;
 
+/// Marks a wall, tree or similar that the player can burst through, either by
+/// rolling into it or by sticking to it and pressing the secondary button.
+/// The strength is the impact speed needed to break it. Meshes and colliders
+/// on this entity and its descendants are removed when it breaks, except for
+/// any [`BrokenVariant`] descendants which are revealed instead.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Breakable {
+    pub strength: f32,
+}
+
+/// Marks the object shown in place of a [`Breakable`] once it has broken.
+/// It's hidden until then.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(on_add = on_broken_variant_added)]
+pub struct BrokenVariant;
+
 pub struct MarkerPlugin;
 
 impl Plugin for MarkerPlugin {
@@ -88,12 +193,45 @@ fn on_trimesh_added(mut world: DeferredWorld, context: HookContext) {
     };
 }
 
+fn on_broken_variant_added(mut world: DeferredWorld, context: HookContext) {
+    world
+        .commands()
+        .entity(context.entity)
+        .insert(Visibility::Hidden);
+}
+
 fn on_water_volume_added(mut world: DeferredWorld, context: HookContext) {
-    let mesh_3d = world.entity(context.entity).get::<Mesh3d>();
+    insert_volume_extents(&mut world, context.entity, "WaterVolume");
+}
+
+fn on_movement_modifier_volume_added(mut world: DeferredWorld, context: HookContext) {
+    insert_volume_extents(&mut world, context.entity, "MovementModifierVolume");
+}
+
+fn on_camera_region_added(mut world: DeferredWorld, context: HookContext) {
+    insert_volume_extents(&mut world, context.entity, "CameraRegion");
+    world
+        .commands()
+        .entity(context.entity)
+        .insert(Visibility::Hidden);
+}
+
+fn on_checkpoint_added(mut world: DeferredWorld, context: HookContext) {
+    insert_volume_extents(&mut world, context.entity, "Checkpoint");
+    world
+        .commands()
+        .entity(context.entity)
+        .insert(Visibility::Hidden);
+}
+
+/// Measure a volume's mesh and insert its [`VolumeExtents`].
+/// The mesh must be a box centred on the entity's origin.
+fn insert_volume_extents(world: &mut DeferredWorld, entity: Entity, volume_name: &str) {
+    let mesh_3d = world.entity(entity).get::<Mesh3d>();
     let Some(mesh_3d) = mesh_3d else {
         eprintln!(
-            "WaterVolume entity {} must have a Mesh3d component",
-            context.entity
+            "{} entity {} must have a Mesh3d component",
+            volume_name, entity
         );
         return;
     };
@@ -101,9 +239,9 @@ fn on_water_volume_added(mut world: DeferredWorld, context: HookContext) {
     let mesh = meshes.get(&mesh_3d.0).unwrap();
     let positions = mesh
         .attribute(Mesh::ATTRIBUTE_POSITION)
-        .expect("A water mesh doesn't have a position attribute")
+        .expect("A volume mesh doesn't have a position attribute")
         .as_float3()
-        .expect("A water mesh's position attribute isn't stored as float3");
+        .expect("A volume mesh's position attribute isn't stored as float3");
 
     let mut min_x: f32 = 0.0;
     let mut max_x: f32 = 0.0;
@@ -120,27 +258,24 @@ fn on_water_volume_added(mut world: DeferredWorld, context: HookContext) {
         max_z = max_z.max(vertex[2]);
     }
     if max_x == 0.0 || max_y == 0.0 || max_z == 0.0 {
-        panic!("Water volume extents are unbalanced around the origin!");
+        panic!("{} extents are unbalanced around the origin!", volume_name);
     }
     if ((min_x / max_x) + 1.0).abs() > 0.01 {
-        panic!("Water volume extents are unbalanced around the origin!");
+        panic!("{} extents are unbalanced around the origin!", volume_name);
     }
     if ((min_y / max_y) + 1.0).abs() > 0.01 {
-        panic!("Water volume extents are unbalanced around the origin!");
+        panic!("{} extents are unbalanced around the origin!", volume_name);
     }
     if ((min_z / max_z) + 1.0).abs() > 0.01 {
-        panic!("Water volume extents are unbalanced around the origin!");
+        panic!("{} extents are unbalanced around the origin!", volume_name);
     }
     println!(
-        "Processed WaterVolume with extents ({}, {}), ({}, {}), ({}, {})",
-        min_x, max_x, min_y, max_y, min_z, max_z
+        "Processed {} with extents ({}, {}), ({}, {}), ({}, {})",
+        volume_name, min_x, max_x, min_y, max_y, min_z, max_z
     );
-    world
-        .commands()
-        .entity(context.entity)
-        .insert(WaterVolumeExtents {
-            half_extent_x: max_x,
-            half_extent_y: max_y,
-            half_extent_z: max_z,
-        });
+    world.commands().entity(entity).insert(VolumeExtents {
+        half_extent_x: max_x,
+        half_extent_y: max_y,
+        half_extent_z: max_z,
+    });
 }
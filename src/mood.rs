@@ -3,7 +3,7 @@ use bevy::{color::palettes::css, prelude::*};
 
 const MOOD_TRANSITION_DURATION: f32 = 2.0;
 
-#[derive(Default, Copy, Clone)]
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
 pub enum Mood {
     #[default]
     Peace,
@@ -74,17 +74,33 @@ impl PhysicalLevelSegment {
 }
 
 #[derive(Message)]
-pub struct NewMood(Mood);
+pub struct NewMood(pub Mood);
 
 #[derive(Default, Resource)]
-struct MoodParams {
+pub struct MoodParams {
     pub current_mood: Mood,
+    pub previous_mood: Mood,
     pub transitioning_from_color: Option<Color>,
     pub transition_progress: f32,
     pub current_physical_segment: PhysicalLevelSegment,
 }
 
 impl MoodParams {
+    /// How far through the transition from the previous mood, from 0 to 1
+    pub fn transition_fraction(&self) -> f32 {
+        match self.transitioning_from_color {
+            Some(_) => self.transition_progress / MOOD_TRANSITION_DURATION,
+            None => 1.0,
+        }
+    }
+
+    fn begin_transition(&mut self, mood: Mood) {
+        self.transitioning_from_color = Some(self.get_current_color());
+        self.previous_mood = self.current_mood;
+        self.current_mood = mood;
+        self.transition_progress = 0.0;
+    }
+
     pub fn get_current_color(&self) -> Color {
         let Some(transitioning_from_color) = self.transitioning_from_color else {
             return self.current_mood.get_background_color();
@@ -119,9 +135,7 @@ impl Plugin for MoodPlugin {
 
 fn process_mood_events(mut mood_params: ResMut<MoodParams>, mut events: MessageReader<NewMood>) {
     for event in events.read() {
-        mood_params.transitioning_from_color = Some(mood_params.get_current_color());
-        mood_params.current_mood = event.0;
-        mood_params.transition_progress = 0.0;
+        mood_params.begin_transition(event.0);
     }
 }
 
@@ -133,9 +147,7 @@ fn process_player_position(
     let segment = PhysicalLevelSegment::for_x(player_transform.translation.x);
     if segment != mood_params.current_physical_segment {
         mood_params.current_physical_segment = segment;
-        mood_params.transitioning_from_color = Some(mood_params.get_current_color());
-        mood_params.current_mood = segment.get_mood();
-        mood_params.transition_progress = 0.0;
+        mood_params.begin_transition(segment.get_mood());
     }
     Ok(())
 }
@@ -0,0 +1,151 @@
+//! The one place the game talks to the MIDI graph. Everything else in the game asks for
+//! changes to the music by sending [`MusicCommand`]s, which are forwarded here.
+
+use bevy::prelude::*;
+use bevy_midi_graph::{
+    MidiGraphAudioContext,
+    midi_graph::{BaseMixer, Config, Event, EventTarget, Message},
+};
+
+/// A change to make to the music playing through the MIDI graph
+#[derive(Message, Debug, Clone, Copy, PartialEq)]
+pub enum MusicCommand {
+    /// Switch to a different stored program (a whole graph)
+    ChangeProgram(usize),
+
+    /// Set the volume of everything in the current program, on its root node so that the
+    /// volumes of the nodes beneath it are kept
+    SetMasterVolume(f32),
+
+    /// Set the volume of one node in the current program
+    SetNodeVolume { node_id: u64, volume: f32 },
+
+    /// Set the tempo of the current program in beats per minute
+    SetTempo(f32),
+
+    /// Set the cutoff frequency of a filter node in the current program
+    SetFilterCutoff { node_id: u64, cutoff_hz: f32 },
+
+    /// Start a note on one node in the current program
+    NoteOn {
+        node_id: u64,
+        note: u8,
+        velocity: f32,
+    },
+
+    /// Release a note started with [`MusicCommand::NoteOn`]
+    NoteOff { node_id: u64, note: u8 },
+}
+
+/// The node at the root of every program, whose volume is the master volume
+const MASTER_NODE_ID: u64 = 0;
+
+/// Read a program's graph from a JSON file and store it under a program number
+pub fn store_program(
+    audio_context: &mut MidiGraphAudioContext,
+    program_no: usize,
+    path: &str,
+) -> Result<(), BevyError> {
+    let config = Config::from_file(path)?;
+    audio_context.store_new_program(program_no, &config)?;
+    Ok(())
+}
+
+/// How much audio the device has played so far, in seconds. This is the clock everything
+/// keeping time with the music follows.
+pub fn audio_position_secs(audio_context: &MidiGraphAudioContext) -> f64 {
+    audio_context.playback_position_secs()
+}
+
+/// The graph message for a command, if it's one that gets sent to the current program
+fn graph_message(command: MusicCommand) -> Option<Message> {
+    let (target, data) = match command {
+        MusicCommand::ChangeProgram(_) => return None,
+        MusicCommand::SetMasterVolume(volume) => (
+            EventTarget::SpecificNode(MASTER_NODE_ID),
+            Event::Volume(volume),
+        ),
+        MusicCommand::SetNodeVolume { node_id, volume } => {
+            (EventTarget::SpecificNode(node_id), Event::Volume(volume))
+        }
+        MusicCommand::SetTempo(beats_per_minute) => {
+            (EventTarget::Broadcast, Event::Tempo(beats_per_minute))
+        }
+        MusicCommand::SetFilterCutoff { node_id, cutoff_hz } => (
+            EventTarget::SpecificNode(node_id),
+            Event::FilterCutoff(cutoff_hz),
+        ),
+        MusicCommand::NoteOn {
+            node_id,
+            note,
+            velocity,
+        } => (
+            EventTarget::SpecificNode(node_id),
+            Event::NoteOn {
+                note,
+                vel: velocity,
+            },
+        ),
+        MusicCommand::NoteOff { node_id, note } => (
+            EventTarget::SpecificNode(node_id),
+            Event::NoteOff { note, vel: 0.0 },
+        ),
+    };
+    Some(Message { target, data })
+}
+
+pub fn forward_music_commands(
+    mut music_commands: MessageReader<MusicCommand>,
+    mut audio_context: ResMut<MidiGraphAudioContext>,
+) {
+    for command in music_commands.read() {
+        match *command {
+            MusicCommand::ChangeProgram(program_no) => {
+                audio_context.change_program(program_no);
+            }
+            command => {
+                if let Some(message) = graph_message(command) {
+                    audio_context.send_event(message);
+                }
+            }
+        }
+    }
+}
+
+/// Runs the graph without an audio device, mixing its output into buffers on demand
+pub struct OfflineRenderer {
+    mixer: BaseMixer,
+}
+
+impl OfflineRenderer {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        Self {
+            mixer: BaseMixer::new(sample_rate as usize, channels as usize),
+        }
+    }
+
+    /// Read a program's graph from a JSON file and store it under a program number
+    pub fn store_program(&mut self, program_no: usize, path: &str) -> Result<(), BevyError> {
+        let config = Config::from_file(path)?;
+        self.mixer.store_program(program_no, &config)?;
+        Ok(())
+    }
+
+    pub fn apply(&mut self, command: MusicCommand) {
+        match command {
+            MusicCommand::ChangeProgram(program_no) => {
+                self.mixer.change_program(program_no);
+            }
+            command => {
+                if let Some(message) = graph_message(command) {
+                    self.mixer.on_event(message);
+                }
+            }
+        }
+    }
+
+    /// Fill a buffer of interleaved samples with what the graph plays next
+    pub fn render(&mut self, buffer: &mut [f32]) {
+        self.mixer.fill_buffer(buffer);
+    }
+}
@@ -0,0 +1,397 @@
+mod graph;
+mod note_bindings;
+mod render;
+mod stingers;
+mod tension;
+
+pub use graph::MusicCommand;
+pub use note_bindings::{MidiNoteBinding, NoteResponse};
+pub use render::{OfflineRenderArgs, render_offline};
+pub use stingers::{Stinger, StingerMap, StingerQuantise, StingerTransition};
+pub use tension::{Tension, TensionMusicMapping, TensionParams, TensionSignals};
+
+use crate::{
+    mood::{Mood, MoodParams},
+    state::AppState,
+};
+use bevy::prelude::*;
+use bevy_midi_graph::{MidiGraphAudioContext, MidiGraphPlugin};
+use graph::{audio_position_secs, forward_music_commands, store_program};
+use note_bindings::{MidiTimelines, apply_note_bindings, load_midi_timelines};
+use std::f32::consts::FRAC_PI_2;
+use stingers::{
+    PendingStingers, ReplacedPlayerStates, StingerCue, on_attachment_inserted,
+    on_attachment_removed, on_attachment_replaced, on_special_move_inserted,
+    on_special_move_removed, on_special_move_replaced, play_due_stingers, schedule_stingers,
+};
+use tension::{TensionPlayback, drive_music_from_tension, update_tension};
+
+/// A graph configuration loaded at startup, and the MIDI file it plays
+struct MusicProgram {
+    program_no: usize,
+    path: &'static str,
+    midi_path: &'static str,
+    beats_per_minute: f64,
+}
+
+const MUSIC_PROGRAMS: [MusicProgram; 2] = [
+    MusicProgram {
+        program_no: 0,
+        path: "assets/music/quest.json",
+        midi_path: "assets/music/quest.mid",
+        beats_per_minute: 96.0,
+    },
+    MusicProgram {
+        program_no: 1,
+        path: "assets/music/resolution.json",
+        midi_path: "assets/music/resolution.mid",
+        beats_per_minute: 84.0,
+    },
+];
+
+/// Where the music is up to, in beats since the current program started. The tempo is the
+/// current program's own tempo, scaled by however much tension is pushing it along. It
+/// moves on by however much audio has actually been played, rather than by frame time, so
+/// that anything keeping time with it stays in sync with what's heard.
+#[derive(Resource, Debug)]
+pub struct MusicClock {
+    pub beats_per_minute: f64,
+    pub beat: f64,
+    program_beats_per_minute: f64,
+    tempo_scale: f64,
+    audio_position_secs: Option<f64>,
+}
+
+impl Default for MusicClock {
+    fn default() -> Self {
+        Self {
+            beats_per_minute: MUSIC_PROGRAMS[0].beats_per_minute,
+            beat: 0.0,
+            program_beats_per_minute: MUSIC_PROGRAMS[0].beats_per_minute,
+            tempo_scale: 1.0,
+            audio_position_secs: None,
+        }
+    }
+}
+
+impl MusicClock {
+    /// The next beat position that falls on a whole number of subdivisions of a beat
+    pub fn next_subdivision(&self, subdivisions_per_beat: u32) -> f64 {
+        let subdivisions = subdivisions_per_beat.max(1) as f64;
+        (self.beat * subdivisions).ceil() / subdivisions
+    }
+
+    fn restart(&mut self, program_no: usize) {
+        self.beat = 0.0;
+        if let Some(program) = MUSIC_PROGRAMS
+            .iter()
+            .find(|program| program.program_no == program_no)
+        {
+            self.program_beats_per_minute = program.beats_per_minute;
+        }
+        self.beats_per_minute = self.program_beats_per_minute * self.tempo_scale;
+    }
+
+    /// Move on by the audio played since the last sync
+    fn sync_to_audio(&mut self, audio_position_secs: f64) {
+        let previous_secs = self.audio_position_secs.unwrap_or(audio_position_secs);
+        let played_secs = (audio_position_secs - previous_secs).max(0.0);
+        self.beat += self.beats_per_minute / 60.0 * played_secs;
+        self.audio_position_secs = Some(audio_position_secs);
+    }
+
+    fn set_tempo_scale(&mut self, tempo_scale: f64) {
+        self.tempo_scale = tempo_scale;
+        self.beats_per_minute = self.program_beats_per_minute * tempo_scale;
+    }
+}
+
+/// Where a mood's music lives in the MIDI graph: either a node within a program, which
+/// can crossfade with other nodes in the same program, or a whole program of its own
+#[derive(Reflect, Debug, Clone, Copy, PartialEq)]
+pub enum MoodTrack {
+    Node { program: usize, node_id: u64 },
+    Program(usize),
+}
+
+impl MoodTrack {
+    fn program(&self) -> usize {
+        match self {
+            Self::Node { program, .. } => *program,
+            Self::Program(program) => *program,
+        }
+    }
+
+    fn node_id(&self) -> Option<u64> {
+        match self {
+            Self::Node { node_id, .. } => Some(*node_id),
+            Self::Program(_) => None,
+        }
+    }
+}
+
+/// The music for each mood. Moving between two nodes of the same program crossfades them
+/// over the mood transition; moving to another program fades out, switches program halfway
+/// through, and fades back in.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct MoodScore {
+    pub peace: MoodTrack,
+    pub confidence: MoodTrack,
+    pub subquest_tension: MoodTrack,
+    pub triumph: MoodTrack,
+    pub relief_after_quest: MoodTrack,
+}
+
+impl Default for MoodScore {
+    fn default() -> Self {
+        Self {
+            peace: MoodTrack::Node {
+                program: 0,
+                node_id: 1,
+            },
+            confidence: MoodTrack::Node {
+                program: 0,
+                node_id: 2,
+            },
+            subquest_tension: MoodTrack::Node {
+                program: 0,
+                node_id: 3,
+            },
+            triumph: MoodTrack::Program(1),
+            relief_after_quest: MoodTrack::Program(1),
+        }
+    }
+}
+
+impl MoodScore {
+    pub fn track(&self, mood: Mood) -> MoodTrack {
+        match mood {
+            Mood::Peace => self.peace,
+            Mood::Confidence => self.confidence,
+            Mood::SubquestTension => self.subquest_tension,
+            Mood::Triumph => self.triumph,
+            Mood::ReliefAfterQuest => self.relief_after_quest,
+        }
+    }
+
+    fn all_tracks(&self) -> [MoodTrack; 5] {
+        [
+            self.peace,
+            self.confidence,
+            self.subquest_tension,
+            self.triumph,
+            self.relief_after_quest,
+        ]
+    }
+}
+
+/// What the MIDI graph was last told, so that only changes get sent
+#[derive(Resource, Default)]
+struct MusicPlayback {
+    program: Option<usize>,
+    master_volume: Option<f32>,
+    node_volumes: Vec<(u64, f32)>,
+}
+
+/// The program, master volume and node volumes the music should have right now
+struct MusicMix {
+    program: usize,
+    master_volume: f32,
+    node_volumes: Vec<(u64, f32)>,
+}
+
+impl MusicMix {
+    fn for_mood(score: &MoodScore, mood_params: &MoodParams) -> Self {
+        let from = score.track(mood_params.previous_mood);
+        let to = score.track(mood_params.current_mood);
+        let fraction = match from == to {
+            true => 1.0,
+            false => mood_params.transition_fraction(),
+        };
+
+        if from.program() == to.program() {
+            let angle = fraction * FRAC_PI_2;
+            let (from_gain, to_gain) = (angle.cos(), angle.sin());
+            return Self {
+                program: to.program(),
+                master_volume: 1.0,
+                node_volumes: Self::node_volumes(score, to.program(), |node_id| {
+                    let mut volume = 0.0;
+                    if from.node_id() == Some(node_id) {
+                        volume += from_gain;
+                    }
+                    if to.node_id() == Some(node_id) {
+                        volume += to_gain;
+                    }
+                    volume
+                }),
+            };
+        }
+
+        let (playing, master_volume) = match fraction < 0.5 {
+            true => (from, 1.0 - 2.0 * fraction),
+            false => (to, 2.0 * fraction - 1.0),
+        };
+        Self {
+            program: playing.program(),
+            master_volume,
+            node_volumes: Self::node_volumes(score, playing.program(), |node_id| {
+                match playing.node_id() == Some(node_id) {
+                    true => 1.0,
+                    false => 0.0,
+                }
+            }),
+        }
+    }
+
+    /// Volumes for every node the score uses in a program
+    fn node_volumes(
+        score: &MoodScore,
+        program: usize,
+        volume_for: impl Fn(u64) -> f32,
+    ) -> Vec<(u64, f32)> {
+        let mut node_volumes: Vec<(u64, f32)> = vec![];
+        for track in score.all_tracks() {
+            if track.program() != program {
+                continue;
+            }
+            if let Some(node_id) = track.node_id()
+                && !node_volumes.iter().any(|(id, _)| *id == node_id)
+            {
+                node_volumes.push((node_id, volume_for(node_id)));
+            }
+        }
+        node_volumes
+    }
+}
+
+/// Music systems that decide what the graph should play, before the commands go out
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+struct ComposeMusicSystems;
+
+/// Plays the music through the audio device
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((MidiGraphPlugin, AdaptiveMusicPlugin))
+            .add_systems(Startup, load_music_programs)
+            .add_systems(
+                Update,
+                (
+                    read_audio_clock.before(ComposeMusicSystems),
+                    forward_music_commands.after(ComposeMusicSystems),
+                ),
+            );
+
+        #[cfg(debug_assertions)]
+        app.add_systems(OnEnter(AppState::Game), tension::spawn_tension_debug_text)
+            .add_systems(OnExit(AppState::Game), tension::remove_tension_debug_text)
+            .add_systems(
+                Update,
+                tension::update_tension_debug_text.run_if(in_state(AppState::Game)),
+            );
+    }
+}
+
+/// Everything that turns the state of the game into [`MusicCommand`]s, wherever they
+/// end up being played
+struct AdaptiveMusicPlugin;
+
+impl Plugin for AdaptiveMusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MoodScore>()
+            .init_resource::<MusicPlayback>()
+            .init_resource::<MusicClock>()
+            .init_resource::<StingerMap>()
+            .init_resource::<PendingStingers>()
+            .init_resource::<ReplacedPlayerStates>()
+            .init_resource::<TensionParams>()
+            .init_resource::<Tension>()
+            .init_resource::<TensionMusicMapping>()
+            .init_resource::<TensionPlayback>()
+            .init_resource::<MidiTimelines>()
+            .add_message::<MusicCommand>()
+            .add_message::<StingerCue>()
+            .add_observer(on_special_move_replaced)
+            .add_observer(on_special_move_removed)
+            .add_observer(on_special_move_inserted)
+            .add_observer(on_attachment_replaced)
+            .add_observer(on_attachment_removed)
+            .add_observer(on_attachment_inserted)
+            .add_systems(Startup, load_midi_timelines)
+            .add_systems(Update, update_tension.before(ComposeMusicSystems))
+            .add_systems(
+                FixedUpdate,
+                apply_note_bindings.run_if(in_state(AppState::Game)),
+            )
+            .add_systems(
+                Update,
+                (
+                    crossfade_mood_music,
+                    drive_music_from_tension,
+                    schedule_stingers,
+                    play_due_stingers,
+                )
+                    .chain()
+                    .in_set(ComposeMusicSystems),
+            );
+    }
+}
+
+/// A program that can't be loaded is reported and skipped, so the game still runs without
+/// its music
+fn load_music_programs(mut audio_context: ResMut<MidiGraphAudioContext>) {
+    for program in MUSIC_PROGRAMS {
+        if let Err(error) = store_program(&mut audio_context, program.program_no, program.path) {
+            eprintln!(
+                "Could not load music program {} from {} (see assets/music/README.md): {}",
+                program.program_no, program.path, error
+            );
+        }
+    }
+}
+
+fn read_audio_clock(mut clock: ResMut<MusicClock>, audio_context: Res<MidiGraphAudioContext>) {
+    clock.sync_to_audio(audio_position_secs(&audio_context));
+}
+
+fn crossfade_mood_music(
+    mut playback: ResMut<MusicPlayback>,
+    mut clock: ResMut<MusicClock>,
+    mut music_commands: MessageWriter<MusicCommand>,
+    mood_params: Res<MoodParams>,
+    score: Res<MoodScore>,
+) {
+    let mix = MusicMix::for_mood(&score, &mood_params);
+
+    if playback.program != Some(mix.program) {
+        music_commands.write(MusicCommand::ChangeProgram(mix.program));
+        playback.program = Some(mix.program);
+        clock.restart(mix.program);
+        playback.master_volume = None;
+        playback.node_volumes.clear();
+    }
+    if playback.master_volume != Some(mix.master_volume) {
+        music_commands.write(MusicCommand::SetMasterVolume(mix.master_volume));
+        playback.master_volume = Some(mix.master_volume);
+    }
+    for (node_id, volume) in mix.node_volumes {
+        let sent = playback
+            .node_volumes
+            .iter_mut()
+            .find(|(id, _)| *id == node_id);
+        match sent {
+            Some((_, sent_volume)) if *sent_volume == volume => {}
+            Some((_, sent_volume)) => {
+                music_commands.write(MusicCommand::SetNodeVolume { node_id, volume });
+                *sent_volume = volume;
+            }
+            None => {
+                music_commands.write(MusicCommand::SetNodeVolume { node_id, volume });
+                playback.node_volumes.push((node_id, volume));
+            }
+        }
+    }
+}
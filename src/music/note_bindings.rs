@@ -0,0 +1,389 @@
+use super::{MUSIC_PROGRAMS, MusicClock, MusicPlayback};
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use midly::{MidiMessage, Smf, Timing, TrackEventKind};
+
+/// How an entity responds to each note-on it's bound to
+#[derive(Reflect, Debug, Clone, Copy)]
+pub enum NoteResponse {
+    /// Jump by the offset from where it was authored, easing back over the decay
+    Move { offset: Vec3, decay_beats: f32 },
+
+    /// Shown, and solid if it has a collider, for a while after each note; hidden otherwise
+    Activate { active_beats: f32 },
+
+    /// Point light intensity multiplied by the scale, easing back over the decay
+    LightPulse {
+        intensity_scale: f32,
+        decay_beats: f32,
+    },
+}
+
+/// Makes a platform, hazard or light keep time with the music. Whenever the MIDI file of
+/// the current program has a note-on for this channel and note, the entity responds. Its
+/// response is worked out afresh each step from the music clock, which follows the audio
+/// played, so that it never drifts from what's heard even when frames drop.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+#[require(NoteBindingRest)]
+pub struct MidiNoteBinding {
+    pub channel: u8,
+    pub note: u8,
+    pub response: NoteResponse,
+}
+
+/// How a [`MidiNoteBinding`] entity was authored, which its responses are relative to
+#[derive(Component, Default)]
+pub struct NoteBindingRest {
+    translation: Option<Vec3>,
+    intensity: Option<f32>,
+    active: Option<bool>,
+}
+
+struct NoteOn {
+    beat: f64,
+    channel: u8,
+    note: u8,
+}
+
+/// The note-ons of one program's MIDI file, in order, which loop along with the program
+struct MidiTimeline {
+    notes: Vec<NoteOn>,
+    length_beats: f64,
+}
+
+impl MidiTimeline {
+    fn parse(bytes: &[u8]) -> Result<Self, BevyError> {
+        let smf = Smf::parse(bytes)?;
+        let ticks_per_beat = match smf.header.timing {
+            Timing::Metrical(ticks_per_beat) => ticks_per_beat.as_int() as f64,
+            Timing::Timecode(..) => return Err("Timecode MIDI timing isn't supported".into()),
+        };
+        let mut notes = vec![];
+        let mut end_tick: u64 = 0;
+        for track in smf.tracks.iter() {
+            let mut tick: u64 = 0;
+            for event in track.iter() {
+                tick += event.delta.as_int() as u64;
+                if let TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOn { key, vel },
+                } = event.kind
+                    && vel.as_int() > 0
+                {
+                    notes.push(NoteOn {
+                        beat: tick as f64 / ticks_per_beat,
+                        channel: channel.as_int(),
+                        note: key.as_int(),
+                    });
+                }
+            }
+            end_tick = end_tick.max(tick);
+        }
+        notes.sort_by(|a, b| a.beat.total_cmp(&b.beat));
+        Ok(Self {
+            notes,
+            length_beats: end_tick as f64 / ticks_per_beat,
+        })
+    }
+
+    /// Beats since the last note-on for a channel and note, counting back into the
+    /// previous loop if the program has already looped
+    fn beats_since_note(&self, channel: u8, note: u8, beat: f64) -> Option<f64> {
+        if self.length_beats <= 0.0 {
+            return None;
+        }
+        let loop_beat = beat.rem_euclid(self.length_beats);
+        let mut matching = self
+            .notes
+            .iter()
+            .filter(|note_on| note_on.channel == channel && note_on.note == note);
+        let last_this_loop = matching
+            .clone()
+            .take_while(|note_on| note_on.beat <= loop_beat)
+            .last();
+        match last_this_loop {
+            Some(note_on) => Some(loop_beat - note_on.beat),
+            None if beat >= self.length_beats => matching
+                .next_back()
+                .map(|note_on| loop_beat + self.length_beats - note_on.beat),
+            None => None,
+        }
+    }
+}
+
+/// MIDI timelines by program number
+#[derive(Resource, Default)]
+pub struct MidiTimelines {
+    timelines: Vec<(usize, MidiTimeline)>,
+}
+
+pub fn load_midi_timelines(mut timelines: ResMut<MidiTimelines>) {
+    for program in MUSIC_PROGRAMS {
+        let timeline = std::fs::read(program.midi_path)
+            .map_err(BevyError::from)
+            .and_then(|bytes| MidiTimeline::parse(&bytes));
+        match timeline {
+            Ok(timeline) => {
+                println!(
+                    "Loaded {} notes over {} beats from {}",
+                    timeline.notes.len(),
+                    timeline.length_beats,
+                    program.midi_path
+                );
+                timelines.timelines.push((program.program_no, timeline));
+            }
+            Err(error) => {
+                println!(
+                    "Could not load MIDI timeline from {}: {}",
+                    program.midi_path, error
+                );
+            }
+        }
+    }
+}
+
+/// How much of a response remains, from 1 right on the note down to 0 once it's decayed
+fn envelope(beats_since_note: Option<f64>, decay_beats: f32) -> f32 {
+    let Some(beats_since_note) = beats_since_note else {
+        return 0.0;
+    };
+    if decay_beats <= 0.0 {
+        return 0.0;
+    }
+    (1.0 - beats_since_note as f32 / decay_beats).clamp(0.0, 1.0)
+}
+
+/// Set every bound entity to where its response puts it at the current beat. Physics bodies
+/// are steered by velocity, as with moving platforms, so that they carry the player along.
+pub fn apply_note_bindings(
+    mut commands: Commands,
+    mut binding_query: Query<(
+        Entity,
+        &MidiNoteBinding,
+        &mut NoteBindingRest,
+        &mut Transform,
+        Option<&mut Visibility>,
+        Option<&mut PointLight>,
+        Option<(&Position, &mut LinearVelocity)>,
+        Has<Collider>,
+    )>,
+    timelines: Res<MidiTimelines>,
+    playback: Res<MusicPlayback>,
+    clock: Res<MusicClock>,
+    time: Res<Time>,
+) {
+    let delta_time = time.delta_secs();
+    let timeline = playback.program.and_then(|program_no| {
+        timelines
+            .timelines
+            .iter()
+            .find(|(timeline_program, _)| *timeline_program == program_no)
+            .map(|(_, timeline)| timeline)
+    });
+    for (
+        entity,
+        binding,
+        mut rest,
+        mut transform,
+        visibility,
+        point_light,
+        physics,
+        has_collider,
+    ) in binding_query.iter_mut()
+    {
+        let beats_since_note = timeline.and_then(|timeline| {
+            timeline.beats_since_note(binding.channel, binding.note, clock.beat)
+        });
+        match binding.response {
+            NoteResponse::Move {
+                offset,
+                decay_beats,
+            } => {
+                let origin = *rest.translation.get_or_insert(transform.translation);
+                let target = origin + envelope(beats_since_note, decay_beats) * offset;
+                match physics {
+                    Some((position, mut linear_velocity)) if delta_time > 0.0 => {
+                        linear_velocity.0 = (target - position.0) / delta_time;
+                    }
+                    Some(_) => {}
+                    None => transform.translation = target,
+                }
+            }
+            NoteResponse::Activate { active_beats } => {
+                let active = beats_since_note
+                    .is_some_and(|beats_since_note| beats_since_note < active_beats as f64);
+                if rest.active == Some(active) {
+                    continue;
+                }
+                rest.active = Some(active);
+                if let Some(mut visibility) = visibility {
+                    *visibility = match active {
+                        true => Visibility::Inherited,
+                        false => Visibility::Hidden,
+                    };
+                }
+                if has_collider {
+                    match active {
+                        true => commands.entity(entity).remove::<ColliderDisabled>(),
+                        false => commands.entity(entity).insert(ColliderDisabled),
+                    };
+                }
+            }
+            NoteResponse::LightPulse {
+                intensity_scale,
+                decay_beats,
+            } => {
+                let Some(mut point_light) = point_light else {
+                    continue;
+                };
+                let rest_intensity = *rest.intensity.get_or_insert(point_light.intensity);
+                let pulse = envelope(beats_since_note, decay_beats);
+                point_light.intensity = rest_intensity * (1.0 + (intensity_scale - 1.0) * pulse);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICKS_PER_BEAT: u16 = 480;
+
+    fn variable_length(mut value: u32) -> Vec<u8> {
+        let mut bytes = vec![(value & 0x7f) as u8];
+        value >>= 7;
+        while value > 0 {
+            bytes.insert(0, (value & 0x7f) as u8 | 0x80);
+            value >>= 7;
+        }
+        bytes
+    }
+
+    /// A single-track MIDI file from pairs of delta ticks and raw event bytes
+    fn midi_file(division: u16, events: &[(u32, &[u8])]) -> Vec<u8> {
+        let mut track = vec![];
+        for (delta, event) in events {
+            track.extend(variable_length(*delta));
+            track.extend_from_slice(event);
+        }
+        let mut bytes = b"MThd".to_vec();
+        bytes.extend(6u32.to_be_bytes());
+        bytes.extend(0u16.to_be_bytes());
+        bytes.extend(1u16.to_be_bytes());
+        bytes.extend(division.to_be_bytes());
+        bytes.extend(b"MTrk");
+        bytes.extend((track.len() as u32).to_be_bytes());
+        bytes.extend(track);
+        bytes
+    }
+
+    fn timeline(notes: &[(f64, u8, u8)], length_beats: f64) -> MidiTimeline {
+        MidiTimeline {
+            notes: notes
+                .iter()
+                .map(|(beat, channel, note)| NoteOn {
+                    beat: *beat,
+                    channel: *channel,
+                    note: *note,
+                })
+                .collect(),
+            length_beats,
+        }
+    }
+
+    #[test]
+    fn parse_reads_note_ons_in_beats() {
+        let bytes = midi_file(
+            TICKS_PER_BEAT,
+            &[
+                (0, &[0x90, 60, 100]),
+                (480, &[0x80, 60, 0]),
+                (0, &[0x99, 36, 90]),
+                // A note-on with no velocity is a note-off
+                (240, &[0x90, 60, 0]),
+                (720, &[0xff, 0x2f, 0x00]),
+            ],
+        );
+        let timeline = MidiTimeline::parse(&bytes).unwrap();
+        let notes: Vec<(f64, u8, u8)> = timeline
+            .notes
+            .iter()
+            .map(|note_on| (note_on.beat, note_on.channel, note_on.note))
+            .collect();
+        assert_eq!(notes, vec![(0.0, 0, 60), (1.0, 9, 36)]);
+        assert_eq!(timeline.length_beats, 3.0);
+    }
+
+    #[test]
+    fn parse_sorts_notes_across_tracks() {
+        let mut bytes = midi_file(
+            TICKS_PER_BEAT,
+            &[(960, &[0x90, 60, 100]), (0, &[0xff, 0x2f, 0x00])],
+        );
+        let second = midi_file(
+            TICKS_PER_BEAT,
+            &[(480, &[0x91, 62, 100]), (960, &[0xff, 0x2f, 0x00])],
+        );
+        // Make it a two-track format 1 file by appending the second file's track chunk
+        bytes[8..10].copy_from_slice(&1u16.to_be_bytes());
+        bytes[10..12].copy_from_slice(&2u16.to_be_bytes());
+        bytes.extend_from_slice(&second[14..]);
+        let timeline = MidiTimeline::parse(&bytes).unwrap();
+        let beats: Vec<f64> = timeline.notes.iter().map(|note_on| note_on.beat).collect();
+        assert_eq!(beats, vec![1.0, 2.0]);
+        assert_eq!(timeline.length_beats, 3.0);
+    }
+
+    #[test]
+    fn parse_rejects_timecode_timing() {
+        let bytes = midi_file(0xe728, &[(0, &[0xff, 0x2f, 0x00])]);
+        assert!(MidiTimeline::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn parse_rejects_garbage() {
+        assert!(MidiTimeline::parse(b"not a midi file").is_err());
+    }
+
+    #[test]
+    fn beats_since_note_counts_from_the_latest_matching_note() {
+        let timeline = timeline(&[(0.0, 0, 60), (1.5, 0, 60), (2.0, 1, 60)], 4.0);
+        assert_eq!(timeline.beats_since_note(0, 60, 0.0), Some(0.0));
+        assert_eq!(timeline.beats_since_note(0, 60, 0.5), Some(0.5));
+        assert_eq!(timeline.beats_since_note(0, 60, 1.75), Some(0.25));
+        assert_eq!(timeline.beats_since_note(0, 60, 3.0), Some(1.5));
+    }
+
+    #[test]
+    fn beats_since_note_matches_channel_and_note() {
+        let timeline = timeline(&[(0.0, 0, 60), (2.0, 1, 60)], 4.0);
+        assert_eq!(timeline.beats_since_note(1, 60, 3.0), Some(1.0));
+        assert_eq!(timeline.beats_since_note(0, 61, 3.0), None);
+    }
+
+    #[test]
+    fn beats_since_note_is_none_before_the_first_note_of_the_first_loop() {
+        let timeline = timeline(&[(2.0, 1, 60)], 4.0);
+        assert_eq!(timeline.beats_since_note(1, 60, 1.0), None);
+    }
+
+    #[test]
+    fn beats_since_note_wraps_back_into_the_previous_loop() {
+        let timeline = timeline(&[(1.0, 0, 60), (2.0, 1, 60), (3.0, 1, 60)], 4.0);
+        // Beat 5 is beat 1 of the second loop, before channel 1's notes, so it's two beats
+        // since the note at beat 3 of the first loop
+        assert_eq!(timeline.beats_since_note(1, 60, 5.0), Some(2.0));
+        // Right on a note in a later loop
+        assert_eq!(timeline.beats_since_note(0, 60, 9.0), Some(0.0));
+        // Just before the loop's only note, nearly a whole loop since the last one
+        assert_eq!(timeline.beats_since_note(0, 60, 8.5), Some(3.5));
+    }
+
+    #[test]
+    fn beats_since_note_is_none_for_an_empty_timeline() {
+        let timeline = timeline(&[(0.0, 0, 60)], 0.0);
+        assert_eq!(timeline.beats_since_note(0, 60, 1.0), None);
+    }
+}
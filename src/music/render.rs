@@ -0,0 +1,223 @@
+//! Renders the adaptive music to a WAV file without a window or audio device, driven by a
+//! simulated timeline of player movement and mood changes. Runs faster than realtime, so
+//! it can run in CI and be compared against a reference render.
+
+use super::{
+    AdaptiveMusicPlugin, ComposeMusicSystems, MUSIC_PROGRAMS, MusicClock, MusicCommand,
+    graph::OfflineRenderer,
+};
+use crate::{
+    markers::Player,
+    mood::{Mood, MoodPlugin, NewMood},
+    state::AppState,
+};
+use bevy::{prelude::*, state::app::StatesPlugin, time::TimeUpdateStrategy};
+use std::time::Duration;
+
+const SAMPLE_RATE: u32 = 44100;
+const CHANNELS: u16 = 2;
+const FRAME_DURATION_SECS: f64 = 1.0 / 60.0;
+const RENDER_DURATION_SECS: f64 = 24.0;
+
+/// Largest RMS difference from the reference render that still counts as a match
+const REFERENCE_TOLERANCE: f32 = 0.01;
+
+enum TimelineEvent {
+    /// Move the player, so that the mood follows whichever level segment they're in
+    PlayerAt(f32),
+    NewMood(Mood),
+}
+
+/// Seconds into the render at which each event happens, in order
+const TIMELINE: [(f64, TimelineEvent); 6] = [
+    (0.0, TimelineEvent::PlayerAt(0.0)),
+    (4.0, TimelineEvent::PlayerAt(7.0)),
+    (8.0, TimelineEvent::PlayerAt(12.0)),
+    (12.0, TimelineEvent::NewMood(Mood::Confidence)),
+    (16.0, TimelineEvent::PlayerAt(17.0)),
+    (20.0, TimelineEvent::PlayerAt(22.0)),
+];
+
+/// Command line arguments for an offline render:
+/// `--render-music <output.wav> [--reference <reference.wav>]`
+pub struct OfflineRenderArgs {
+    pub output_path: String,
+    pub reference_path: Option<String>,
+}
+
+impl OfflineRenderArgs {
+    /// The offline render requested on the command line, if there is one
+    pub fn from_env() -> Option<Self> {
+        let args: Vec<String> = std::env::args().collect();
+        let value_after = |flag: &str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .and_then(|index| args.get(index + 1))
+                .cloned()
+        };
+        Some(Self {
+            output_path: value_after("--render-music")?,
+            reference_path: value_after("--reference"),
+        })
+    }
+}
+
+#[derive(Resource)]
+struct OfflineRender {
+    renderer: OfflineRenderer,
+    samples: Vec<f32>,
+    frames_owed: f64,
+    next_timeline_event: usize,
+}
+
+fn play_timeline(
+    mut render: ResMut<OfflineRender>,
+    mut player_query: Query<&mut Transform, With<Player>>,
+    mut new_moods: MessageWriter<NewMood>,
+    time: Res<Time>,
+) {
+    while let Some((at_secs, event)) = TIMELINE.get(render.next_timeline_event) {
+        if *at_secs > time.elapsed_secs_f64() {
+            break;
+        }
+        match event {
+            TimelineEvent::PlayerAt(x) => {
+                for mut transform in player_query.iter_mut() {
+                    transform.translation.x = *x;
+                }
+            }
+            TimelineEvent::NewMood(mood) => {
+                new_moods.write(NewMood(*mood));
+            }
+        }
+        render.next_timeline_event += 1;
+    }
+}
+
+/// Keep the music clock in time with the samples rendered so far, as the audio device
+/// would when playing live
+fn read_rendered_clock(render: Res<OfflineRender>, mut clock: ResMut<MusicClock>) {
+    let rendered_frames = render.samples.len() / CHANNELS as usize;
+    clock.sync_to_audio(rendered_frames as f64 / SAMPLE_RATE as f64);
+}
+
+fn render_music_frame(
+    mut render: ResMut<OfflineRender>,
+    mut music_commands: MessageReader<MusicCommand>,
+    time: Res<Time>,
+) {
+    for command in music_commands.read() {
+        render.renderer.apply(*command);
+    }
+    render.frames_owed += time.delta_secs_f64() * SAMPLE_RATE as f64;
+    let frames = render.frames_owed.floor();
+    render.frames_owed -= frames;
+
+    let mut buffer = vec![0.0; frames as usize * CHANNELS as usize];
+    render.renderer.render(&mut buffer);
+    render.samples.extend_from_slice(&buffer);
+}
+
+fn write_wav(path: &str, samples: &[f32]) -> Result<(), BevyError> {
+    let spec = hound::WavSpec {
+        channels: CHANNELS,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for sample in samples {
+        writer.write_sample(*sample)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+fn compare_with_reference(path: &str, samples: &[f32]) -> Result<(), BevyError> {
+    let mut reader = hound::WavReader::open(path)?;
+    let reference = reader.samples::<f32>().collect::<Result<Vec<f32>, _>>()?;
+    if reference.len() != samples.len() {
+        return Err(format!(
+            "Render has {} samples but reference {} has {}",
+            samples.len(),
+            path,
+            reference.len()
+        )
+        .into());
+    }
+    let squared_error: f32 = samples
+        .iter()
+        .zip(reference.iter())
+        .map(|(sample, reference_sample)| (sample - reference_sample).powi(2))
+        .sum();
+    let rms_difference = (squared_error / samples.len().max(1) as f32).sqrt();
+    println!("RMS difference from {}: {}", path, rms_difference);
+    if rms_difference > REFERENCE_TOLERANCE {
+        return Err(format!(
+            "Render differs from reference {} by more than {}",
+            path, REFERENCE_TOLERANCE
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Run the mood and music systems on a fixed timestep through the simulated timeline,
+/// write what the graph played to a WAV file, and check it against a reference if given
+pub fn render_offline(args: &OfflineRenderArgs) -> Result<(), BevyError> {
+    let mut renderer = OfflineRenderer::new(SAMPLE_RATE, CHANNELS);
+    for program in MUSIC_PROGRAMS {
+        renderer
+            .store_program(program.program_no, program.path)
+            .map_err(|error| {
+                format!(
+                    "Could not load music program {} from {} (see assets/music/README.md): {}",
+                    program.program_no, program.path, error
+                )
+            })?;
+    }
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, StatesPlugin))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            FRAME_DURATION_SECS,
+        )))
+        .insert_resource(ClearColor::default())
+        .insert_state(AppState::Game)
+        .add_plugins((MoodPlugin, AdaptiveMusicPlugin))
+        .insert_resource(OfflineRender {
+            renderer,
+            samples: vec![],
+            frames_owed: 0.0,
+            next_timeline_event: 0,
+        })
+        .add_systems(
+            Update,
+            (
+                (play_timeline, read_rendered_clock).before(ComposeMusicSystems),
+                render_music_frame.after(ComposeMusicSystems),
+            ),
+        );
+    app.world_mut()
+        .spawn((Name::new("Simulated player"), Player, Transform::default()));
+
+    let frame_count = (RENDER_DURATION_SECS / FRAME_DURATION_SECS).ceil() as usize;
+    for _ in 0..frame_count {
+        app.update();
+    }
+
+    let render = app
+        .world_mut()
+        .remove_resource::<OfflineRender>()
+        .ok_or("Offline render resource went missing")?;
+    write_wav(&args.output_path, &render.samples)?;
+    println!(
+        "Rendered {} seconds of music to {}",
+        render.samples.len() as f64 / (SAMPLE_RATE as f64 * CHANNELS as f64),
+        args.output_path
+    );
+    if let Some(reference_path) = &args.reference_path {
+        compare_with_reference(reference_path, &render.samples)?;
+    }
+    Ok(())
+}
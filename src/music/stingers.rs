@@ -0,0 +1,263 @@
+use super::{MusicClock, MusicCommand, MusicPlayback};
+use crate::controller::{Attachment, PlayerController, SpecialMove};
+use bevy::prelude::*;
+use std::mem::{Discriminant, discriminant};
+
+/// Player transitions that can set off a stinger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StingerTransition {
+    Jump,
+    HardLanding,
+    WallStick,
+    WaterEntry,
+    Dive,
+}
+
+/// Sent when the player makes a transition, to play its stinger if it has one
+#[derive(Message, Debug, Clone, Copy)]
+pub struct StingerCue(pub StingerTransition);
+
+/// When a stinger plays, relative to the music clock
+#[derive(Reflect, Debug, Clone, Copy, PartialEq)]
+pub enum StingerQuantise {
+    Immediate,
+    NextBeat,
+    NextSubdivision { subdivisions_per_beat: u32 },
+}
+
+/// A short phrase played on a node of the current program
+#[derive(Reflect, Debug, Clone, Copy)]
+pub struct Stinger {
+    pub node_id: u64,
+    pub note: u8,
+    pub velocity: f32,
+
+    /// How long the note is held, in beats
+    pub length_beats: f64,
+    pub quantise: StingerQuantise,
+}
+
+/// Which stinger, if any, each player transition plays
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct StingerMap {
+    pub jump: Option<Stinger>,
+    pub hard_landing: Option<Stinger>,
+    pub wall_stick: Option<Stinger>,
+    pub water_entry: Option<Stinger>,
+    pub dive: Option<Stinger>,
+}
+
+const STINGER_NODE_ID: u64 = 100;
+
+impl Default for StingerMap {
+    fn default() -> Self {
+        let stinger = |note, velocity, length_beats, quantise| {
+            Some(Stinger {
+                node_id: STINGER_NODE_ID,
+                note,
+                velocity,
+                length_beats,
+                quantise,
+            })
+        };
+        Self {
+            jump: stinger(
+                72,
+                0.5,
+                0.25,
+                StingerQuantise::NextSubdivision {
+                    subdivisions_per_beat: 4,
+                },
+            ),
+            hard_landing: stinger(48, 0.9, 1.0, StingerQuantise::NextBeat),
+            wall_stick: stinger(
+                67,
+                0.6,
+                0.5,
+                StingerQuantise::NextSubdivision {
+                    subdivisions_per_beat: 2,
+                },
+            ),
+            water_entry: stinger(60, 0.7, 2.0, StingerQuantise::NextBeat),
+            dive: stinger(
+                76,
+                0.6,
+                0.5,
+                StingerQuantise::NextSubdivision {
+                    subdivisions_per_beat: 4,
+                },
+            ),
+        }
+    }
+}
+
+impl StingerMap {
+    fn stinger(&self, transition: StingerTransition) -> Option<&Stinger> {
+        match transition {
+            StingerTransition::Jump => self.jump.as_ref(),
+            StingerTransition::HardLanding => self.hard_landing.as_ref(),
+            StingerTransition::WallStick => self.wall_stick.as_ref(),
+            StingerTransition::WaterEntry => self.water_entry.as_ref(),
+            StingerTransition::Dive => self.dive.as_ref(),
+        }
+    }
+}
+
+/// Stinger notes waiting for their time, in beats of the music clock. Beats start again
+/// from zero when the program changes, so notes yet to start are dropped then, and notes
+/// already playing are released straight away.
+#[derive(Resource, Default)]
+pub struct PendingStingers {
+    program: Option<usize>,
+    commands: Vec<(f64, MusicCommand)>,
+}
+
+/// The kind of player state an insert is replacing, so that inserting the same state again
+/// isn't mistaken for a transition into it
+#[derive(Resource, Default)]
+pub struct ReplacedPlayerStates {
+    special_move: Option<Discriminant<SpecialMove>>,
+    attachment: Option<Discriminant<Attachment>>,
+}
+
+pub fn on_special_move_replaced(
+    replace: On<Replace, SpecialMove>,
+    special_move_query: Query<&SpecialMove, With<PlayerController>>,
+    mut replaced: ResMut<ReplacedPlayerStates>,
+) {
+    replaced.special_move = special_move_query
+        .get(replace.entity)
+        .ok()
+        .map(discriminant);
+}
+
+/// A removal replaces the state with nothing, so whatever comes next is a transition
+pub fn on_special_move_removed(
+    _remove: On<Remove, SpecialMove>,
+    mut replaced: ResMut<ReplacedPlayerStates>,
+) {
+    replaced.special_move = None;
+}
+
+pub fn on_special_move_inserted(
+    insert: On<Insert, SpecialMove>,
+    special_move_query: Query<&SpecialMove, With<PlayerController>>,
+    mut replaced: ResMut<ReplacedPlayerStates>,
+    mut cues: MessageWriter<StingerCue>,
+) {
+    let previous = replaced.special_move.take();
+    let Ok(special_move) = special_move_query.get(insert.entity) else {
+        return;
+    };
+    if previous == Some(discriminant(special_move)) {
+        return;
+    }
+    let transition = match special_move {
+        SpecialMove::Jumping => StingerTransition::Jump,
+        SpecialMove::Diving => StingerTransition::Dive,
+        SpecialMove::Landing { progress } if *progress == 0.0 => StingerTransition::HardLanding,
+        _ => return,
+    };
+    cues.write(StingerCue(transition));
+}
+
+pub fn on_attachment_replaced(
+    replace: On<Replace, Attachment>,
+    attachment_query: Query<&Attachment, With<PlayerController>>,
+    mut replaced: ResMut<ReplacedPlayerStates>,
+) {
+    replaced.attachment = attachment_query.get(replace.entity).ok().map(discriminant);
+}
+
+pub fn on_attachment_removed(
+    _remove: On<Remove, Attachment>,
+    mut replaced: ResMut<ReplacedPlayerStates>,
+) {
+    replaced.attachment = None;
+}
+
+pub fn on_attachment_inserted(
+    insert: On<Insert, Attachment>,
+    attachment_query: Query<&Attachment, With<PlayerController>>,
+    mut replaced: ResMut<ReplacedPlayerStates>,
+    mut cues: MessageWriter<StingerCue>,
+) {
+    let previous = replaced.attachment.take();
+    let Ok(attachment) = attachment_query.get(insert.entity) else {
+        return;
+    };
+    if previous == Some(discriminant(attachment)) {
+        return;
+    }
+    let transition = match attachment {
+        Attachment::Walled { progress, .. } if *progress == 0.0 => StingerTransition::WallStick,
+        Attachment::Submerged { .. } => StingerTransition::WaterEntry,
+        _ => return,
+    };
+    cues.write(StingerCue(transition));
+}
+
+/// Work out on which beat each cued stinger should play, by waiting for the music clock
+/// to reach the next beat or subdivision if the stinger asks for it
+pub fn schedule_stingers(
+    mut cues: MessageReader<StingerCue>,
+    mut pending: ResMut<PendingStingers>,
+    stinger_map: Res<StingerMap>,
+    clock: Res<MusicClock>,
+    playback: Res<MusicPlayback>,
+    mut music_commands: MessageWriter<MusicCommand>,
+) {
+    if pending.program != playback.program {
+        pending.program = playback.program;
+        for (_, command) in pending.commands.drain(..) {
+            if let MusicCommand::NoteOff { .. } = command {
+                music_commands.write(command);
+            }
+        }
+    }
+    for StingerCue(transition) in cues.read() {
+        let Some(stinger) = stinger_map.stinger(*transition) else {
+            continue;
+        };
+        let start_beat = match stinger.quantise {
+            StingerQuantise::Immediate => clock.beat,
+            StingerQuantise::NextBeat => clock.next_subdivision(1),
+            StingerQuantise::NextSubdivision {
+                subdivisions_per_beat,
+            } => clock.next_subdivision(subdivisions_per_beat),
+        };
+        let end_beat = start_beat + stinger.length_beats;
+        pending.commands.push((
+            start_beat,
+            MusicCommand::NoteOn {
+                node_id: stinger.node_id,
+                note: stinger.note,
+                velocity: stinger.velocity,
+            },
+        ));
+        pending.commands.push((
+            end_beat,
+            MusicCommand::NoteOff {
+                node_id: stinger.node_id,
+                note: stinger.note,
+            },
+        ));
+    }
+}
+
+/// Send the stinger notes the music clock has reached, which follows the audio actually
+/// played rather than the frame time
+pub fn play_due_stingers(
+    mut pending: ResMut<PendingStingers>,
+    mut music_commands: MessageWriter<MusicCommand>,
+    clock: Res<MusicClock>,
+) {
+    pending.commands.retain(|(due_beat, command)| {
+        if *due_beat > clock.beat {
+            return true;
+        }
+        music_commands.write(*command);
+        false
+    });
+}
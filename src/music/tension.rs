@@ -0,0 +1,280 @@
+use super::{MusicClock, MusicCommand, MusicPlayback};
+use crate::{
+    controller::CharacterController,
+    markers::{CarriedItem, Chaser, Checkpoint, Player, VolumeExtents},
+};
+use bevy::prelude::*;
+
+/// How much each gameplay signal adds to the tension, and the ranges over which they build
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct TensionParams {
+    pub chaser_weight: f32,
+    pub speed_weight: f32,
+    pub checkpoint_weight: f32,
+    pub carried_item_weight: f32,
+
+    /// Distance at which a chaser adds its full weight
+    pub chaser_near_distance: f32,
+
+    /// Distance beyond which a chaser adds nothing
+    pub chaser_far_distance: f32,
+
+    /// Player speed at which speed adds its full weight
+    pub full_tension_speed: f32,
+
+    /// Seconds since the last checkpoint at which the wait adds its full weight
+    pub checkpoint_build_up_secs: f32,
+
+    /// Rate at which the tension rises towards its target; higher is snappier
+    pub rise_smoothing: f32,
+
+    /// Rate at which the tension falls back towards its target
+    pub fall_smoothing: f32,
+}
+
+impl Default for TensionParams {
+    fn default() -> Self {
+        Self {
+            chaser_weight: 0.6,
+            speed_weight: 0.2,
+            checkpoint_weight: 0.3,
+            carried_item_weight: 0.4,
+            chaser_near_distance: 3.0,
+            chaser_far_distance: 25.0,
+            full_tension_speed: 8.0,
+            checkpoint_build_up_secs: 90.0,
+            rise_smoothing: 2.0,
+            fall_smoothing: 0.5,
+        }
+    }
+}
+
+/// Each gameplay signal's share of the tension, from 0 to 1 before weighting
+#[derive(Reflect, Debug, Default, Clone, Copy)]
+pub struct TensionSignals {
+    pub chaser: f32,
+    pub speed: f32,
+    pub checkpoint: f32,
+    pub carried_item: f32,
+}
+
+/// How intense things are right now, from 0 to 1. Smoothed, so that the music swells and
+/// settles rather than jumping with every change in the signals.
+#[derive(Resource, Reflect, Debug, Default)]
+#[reflect(Resource)]
+pub struct Tension {
+    pub value: f32,
+    pub target: f32,
+    pub signals: TensionSignals,
+    pub since_checkpoint_secs: f32,
+}
+
+/// Which MIDI graph parameters the tension drives, and over what ranges
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct TensionMusicMapping {
+    /// Tempo multiplier at no tension and at full tension
+    pub min_tempo_scale: f32,
+    pub max_tempo_scale: f32,
+
+    /// A node whose volume fades in with tension, such as a percussion layer
+    pub layer_node_id: Option<u64>,
+
+    /// A filter node that opens up with tension
+    pub filter_node_id: Option<u64>,
+    pub min_filter_cutoff_hz: f32,
+    pub max_filter_cutoff_hz: f32,
+
+    /// Smallest change in tension worth sending to the graph
+    pub send_threshold: f32,
+}
+
+impl Default for TensionMusicMapping {
+    fn default() -> Self {
+        Self {
+            min_tempo_scale: 1.0,
+            max_tempo_scale: 1.15,
+            layer_node_id: Some(10),
+            filter_node_id: Some(20),
+            min_filter_cutoff_hz: 800.0,
+            max_filter_cutoff_hz: 12000.0,
+            send_threshold: 0.01,
+        }
+    }
+}
+
+/// The tension last sent to the graph, and in which program
+#[derive(Resource, Default)]
+pub struct TensionPlayback {
+    value: Option<f32>,
+    program: Option<usize>,
+}
+
+/// Fraction of the way from `near` to `far`, clamped to 0 to 1
+fn ramp(value: f32, near: f32, far: f32) -> f32 {
+    ((value - near) / (far - near).max(f32::EPSILON)).clamp(0.0, 1.0)
+}
+
+fn is_inside(point: Vec3, volume_transform: &GlobalTransform, extents: &VolumeExtents) -> bool {
+    let relative = (point - volume_transform.translation()).abs();
+    relative.x <= extents.half_extent_x
+        && relative.y <= extents.half_extent_y
+        && relative.z <= extents.half_extent_z
+}
+
+pub fn update_tension(
+    mut tension: ResMut<Tension>,
+    player_query: Query<(&Transform, Option<&CharacterController>), With<Player>>,
+    chaser_query: Query<&GlobalTransform, With<Chaser>>,
+    checkpoint_query: Query<(&GlobalTransform, &VolumeExtents), With<Checkpoint>>,
+    carried_item_query: Query<&CarriedItem>,
+    params: Res<TensionParams>,
+    time: Res<Time>,
+) {
+    let delta_time = time.delta_secs();
+    let Ok((player_transform, controller)) = player_query.single() else {
+        return;
+    };
+    let player_translation = player_transform.translation;
+
+    tension.since_checkpoint_secs = match checkpoint_query
+        .iter()
+        .any(|(transform, extents)| is_inside(player_translation, transform, extents))
+    {
+        true => 0.0,
+        false => tension.since_checkpoint_secs + delta_time,
+    };
+
+    let nearest_chaser = chaser_query
+        .iter()
+        .map(|transform| transform.translation().distance(player_translation))
+        .reduce(f32::min);
+    let speed = controller.map_or(0.0, |controller| controller.velocity().length());
+    let carried_item_damage = carried_item_query
+        .iter()
+        .map(|item| 1.0 - (item.health / item.max_health.max(f32::EPSILON)).clamp(0.0, 1.0))
+        .reduce(f32::max);
+
+    let signals = TensionSignals {
+        chaser: nearest_chaser.map_or(0.0, |distance| {
+            1.0 - ramp(
+                distance,
+                params.chaser_near_distance,
+                params.chaser_far_distance,
+            )
+        }),
+        speed: ramp(speed, 0.0, params.full_tension_speed),
+        checkpoint: ramp(
+            tension.since_checkpoint_secs,
+            0.0,
+            params.checkpoint_build_up_secs,
+        ),
+        carried_item: carried_item_damage.unwrap_or(0.0),
+    };
+    tension.signals = signals;
+    tension.target = (params.chaser_weight * signals.chaser
+        + params.speed_weight * signals.speed
+        + params.checkpoint_weight * signals.checkpoint
+        + params.carried_item_weight * signals.carried_item)
+        .clamp(0.0, 1.0);
+
+    let smoothing = match tension.target > tension.value {
+        true => params.rise_smoothing,
+        false => params.fall_smoothing,
+    };
+    let catch_up = 1.0 - (-smoothing * delta_time).exp();
+    tension.value += (tension.target - tension.value) * catch_up;
+}
+
+/// Push the tempo, the tension layer's volume and the filter cutoff along with the tension,
+/// whenever it has changed enough to hear or the program has changed underneath it
+pub fn drive_music_from_tension(
+    mut tension_playback: ResMut<TensionPlayback>,
+    mut clock: ResMut<MusicClock>,
+    mut music_commands: MessageWriter<MusicCommand>,
+    playback: Res<MusicPlayback>,
+    tension: Res<Tension>,
+    mapping: Res<TensionMusicMapping>,
+) {
+    let value = tension.value;
+    let changed_enough = tension_playback
+        .value
+        .is_none_or(|sent| (sent - value).abs() >= mapping.send_threshold);
+    if !changed_enough && tension_playback.program == playback.program {
+        return;
+    }
+    tension_playback.value = Some(value);
+    tension_playback.program = playback.program;
+
+    let tempo_scale =
+        mapping.min_tempo_scale + (mapping.max_tempo_scale - mapping.min_tempo_scale) * value;
+    clock.set_tempo_scale(tempo_scale as f64);
+    music_commands.write(MusicCommand::SetTempo(clock.beats_per_minute as f32));
+    if let Some(node_id) = mapping.layer_node_id {
+        music_commands.write(MusicCommand::SetNodeVolume {
+            node_id,
+            volume: value,
+        });
+    }
+    if let Some(node_id) = mapping.filter_node_id {
+        // Cutoff sweeps evenly in pitch rather than in hertz
+        let cutoff_hz = mapping.min_filter_cutoff_hz
+            * (mapping.max_filter_cutoff_hz / mapping.min_filter_cutoff_hz).powf(value);
+        music_commands.write(MusicCommand::SetFilterCutoff { node_id, cutoff_hz });
+    }
+}
+
+/// Debug readout of the tension and the signals behind it
+#[cfg(debug_assertions)]
+#[derive(Component)]
+pub struct TensionDebugText;
+
+#[cfg(debug_assertions)]
+pub fn spawn_tension_debug_text(mut commands: Commands) {
+    commands.spawn((
+        TensionDebugText,
+        bevy::camera::visibility::RenderLayers::layer(crate::app_draw_layer::HUD),
+        Text::default(),
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(16.0),
+            left: Val::Px(16.0),
+            ..default()
+        },
+    ));
+}
+
+#[cfg(debug_assertions)]
+pub fn remove_tension_debug_text(
+    mut commands: Commands,
+    text_query: Query<Entity, With<TensionDebugText>>,
+) {
+    for entity in text_query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(debug_assertions)]
+pub fn update_tension_debug_text(
+    mut text_query: Query<&mut Text, With<TensionDebugText>>,
+    tension: Res<Tension>,
+) {
+    let signals = tension.signals;
+    for mut text in text_query.iter_mut() {
+        text.0 = format!(
+            "Tension {:.2} (target {:.2})\nchaser {:.2}  speed {:.2}  checkpoint {:.2}  item {:.2}",
+            tension.value,
+            tension.target,
+            signals.chaser,
+            signals.speed,
+            signals.checkpoint,
+            signals.carried_item
+        );
+    }
+}
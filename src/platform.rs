@@ -0,0 +1,67 @@
+use crate::state::AppState;
+use avian3d::{math::Vector, prelude::*};
+use bevy::prelude::*;
+
+/// A kinematic body that carries the player while they stand on it. It travels back and
+/// forth between where it was authored and that position plus the travel offset, and
+/// spins at a constant angular velocity about its origin. Put this on the object and
+/// the Trimesh marker on its mesh, as the collider then attaches to this body.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+#[require(RigidBody::Kinematic, PlatformRest)]
+pub struct MovingPlatform {
+    pub travel: Vector,
+    pub period_secs: f32,
+    pub angular_velocity: Vector,
+}
+
+/// Where a [`MovingPlatform`] was authored, which its travel is relative to
+#[derive(Component, Default)]
+pub struct PlatformRest {
+    translation: Option<Vector>,
+}
+
+pub struct PlatformPlugin;
+
+impl Plugin for PlatformPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            drive_platforms.run_if(in_state(AppState::Game)),
+        );
+    }
+}
+
+/// Set platform velocities so that the physics step moves them along their paths.
+/// Steering towards the next point on the path, rather than using the path's derivative,
+/// keeps platforms from drifting over time.
+fn drive_platforms(
+    mut platform_query: Query<(
+        &MovingPlatform,
+        &mut PlatformRest,
+        &Position,
+        &mut LinearVelocity,
+        &mut AngularVelocity,
+    )>,
+    time: Res<Time>,
+) {
+    let delta_time = time.delta_secs();
+    if delta_time == 0.0 {
+        return;
+    }
+    for (platform, mut rest, position, mut linear_velocity, mut angular_velocity) in
+        platform_query.iter_mut()
+    {
+        let origin = *rest.translation.get_or_insert(position.0);
+        let target = match platform.period_secs > 0.0 {
+            true => {
+                let cycle = (time.elapsed_secs() + delta_time) / platform.period_secs;
+                let progress = 0.5 - 0.5 * (std::f32::consts::TAU * cycle).cos();
+                origin + progress * platform.travel
+            }
+            false => origin,
+        };
+        linear_velocity.0 = (target - position.0) / delta_time;
+        angular_velocity.0 = platform.angular_velocity;
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{
+    markers::{VolumeExtents, WaterVolume},
+    state::AppState,
+};
+use bevy::prelude::*;
+
+/// Smallest half-height a water volume can shrink to when the tide goes out
+const MIN_TIDE_HALF_EXTENT_Y: f32 = 0.01;
+
+/// Makes a water volume's surface rise and fall. The bottom of the volume stays
+/// where it was authored; the volume is stretched vertically so that its top
+/// sits at the authored height plus the current tide offset.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+#[require(TideLevel)]
+pub struct Tide {
+    pub curve: TideCurve,
+}
+
+#[derive(Reflect, Debug, Clone, Copy)]
+pub enum TideCurve {
+    /// Follow a sine wave over time. A period of zero or less keeps the water still.
+    Periodic {
+        amplitude: f32,
+        period_secs: f32,
+        phase_secs: f32,
+    },
+    /// Move towards [`TideLevel::target_offset`] at a fixed rate, for scripted events to set
+    Scripted { rate: f32 },
+}
+
+/// Runtime state of a [`Tide`], relative to how the water volume was authored
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component, Default)]
+pub struct TideLevel {
+    /// Current height of the surface above its authored height
+    pub offset: f32,
+    /// The offset a [`TideCurve::Scripted`] tide is moving towards
+    pub target_offset: f32,
+    rest: Option<TideRest>,
+}
+
+#[derive(Reflect, Debug, Clone, Copy)]
+struct TideRest {
+    translation_y: f32,
+    scale_y: f32,
+    half_extent_y: f32,
+}
+
+pub struct TidePlugin;
+
+impl Plugin for TidePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, update_tides.run_if(in_state(AppState::Game)));
+    }
+}
+
+fn update_tides(
+    mut tide_query: Query<
+        (&Tide, &mut TideLevel, &mut Transform, &mut VolumeExtents),
+        With<WaterVolume>,
+    >,
+    time: Res<Time>,
+) {
+    for (tide, mut level, mut transform, mut extents) in tide_query.iter_mut() {
+        let rest = *level.rest.get_or_insert(TideRest {
+            translation_y: transform.translation.y,
+            scale_y: transform.scale.y,
+            half_extent_y: extents.half_extent_y,
+        });
+
+        level.offset = match tide.curve {
+            TideCurve::Periodic {
+                amplitude,
+                period_secs,
+                phase_secs,
+            } if period_secs > 0.0 => {
+                let cycle = (time.elapsed_secs() + phase_secs) / period_secs;
+                amplitude * (std::f32::consts::TAU * cycle).sin()
+            }
+            TideCurve::Periodic { .. } => 0.0,
+            TideCurve::Scripted { rate } => {
+                let remaining = level.target_offset - level.offset;
+                let step = rate * time.delta_secs();
+                match remaining.abs() <= step {
+                    true => level.target_offset,
+                    false => level.offset + step * remaining.signum(),
+                }
+            }
+        };
+
+        // Stretch about the bottom of the volume, so only the surface moves
+        let half_extent_y = (rest.half_extent_y + 0.5 * level.offset).max(MIN_TIDE_HALF_EXTENT_Y);
+        let stretch = half_extent_y - rest.half_extent_y;
+        extents.half_extent_y = half_extent_y;
+        transform.translation.y = rest.translation_y + stretch;
+        transform.scale.y = rest.scale_y * half_extent_y / rest.half_extent_y;
+    }
+}